@@ -0,0 +1,316 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Fields, LitInt, LitStr, Type, parse_macro_input};
+
+/// Telegram's limit for `callback_data`.
+const CALLBACK_LIMIT: usize = 64;
+
+/// Derive `ActionCodec` for an enum of unit, integer,
+/// `bool` and bounded `String` variants.
+///
+/// Encoded as `{prefix}:{tag}[:{field}...]`; strings are cut
+/// to `max_len` bytes and escaped with `telegram_botkit::scene::escape_field`.
+#[proc_macro_derive(ActionCodec, attributes(action))]
+pub fn derive_action_codec(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+enum Kind {
+    Int(usize),
+    Bool,
+    Str(usize),
+}
+
+struct Field {
+    kind: Kind,
+    ty: Type,
+}
+
+struct Variant {
+    ident: syn::Ident,
+    tag: String,
+    fields: Vec<Field>,
+    named: Option<Vec<syn::Ident>>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "ActionCodec can only be derived for enums",
+        ));
+    };
+
+    let mut variants = Vec::with_capacity(data.variants.len());
+    for v in &data.variants {
+        let tag = match attr_str(&v.attrs, "rename")? {
+            Some(tag) => tag,
+            None => snake_case(&v.ident.to_string()),
+        };
+        if tag.is_empty() || tag.contains([':', '%']) || !tag.is_ascii() {
+            return Err(syn::Error::new_spanned(
+                &v.ident,
+                "action tag must be non-empty ASCII without ':' or '%'",
+            ));
+        }
+
+        let mut fields = Vec::new();
+        for f in v.fields.iter() {
+            fields.push(Field {
+                kind: field_kind(f)?,
+                ty: f.ty.clone(),
+            });
+        }
+
+        let named = match &v.fields {
+            Fields::Named(n) => Some(n.named.iter().filter_map(|f| f.ident.clone()).collect()),
+            _ => None,
+        };
+
+        variants.push(Variant {
+            ident: v.ident.clone(),
+            tag,
+            fields,
+            named,
+        });
+    }
+
+    for (i, a) in variants.iter().enumerate() {
+        if let Some(b) = variants[..i].iter().find(|b| b.tag == a.tag) {
+            return Err(syn::Error::new_spanned(
+                &a.ident,
+                format!("action tag `{}` is also used by `{}`", a.tag, b.ident),
+            ));
+        }
+    }
+
+    // Worst case without the prefix: ":" + tag + ":" + field per field
+    let max_len = variants
+        .iter()
+        .map(|v| {
+            1 + v.tag.len()
+                + v.fields
+                    .iter()
+                    .map(|f| {
+                        1 + match f.kind {
+                            Kind::Int(n) => n,
+                            Kind::Bool => 1,
+                            Kind::Str(n) => 3 * n,
+                        }
+                    })
+                    .sum::<usize>()
+        })
+        .max()
+        .unwrap_or(0);
+
+    if max_len >= CALLBACK_LIMIT {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            format!(
+                "worst-case action encoding is {max_len} bytes before the scene prefix, \
+                 over Telegram's {CALLBACK_LIMIT}-byte callback limit"
+            ),
+        ));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let encode_arms = variants.iter().map(encode_arm);
+    let decode_arms = variants.iter().map(decode_arm);
+
+    Ok(quote! {
+        impl #impl_generics ::telegram_botkit::scene::ActionCodec for #ident #ty_generics #where_clause {
+            const MAX_ENCODED_LEN: ::core::option::Option<usize> =
+                ::core::option::Option::Some(#max_len);
+
+            fn encode(&self, prefix: &str) -> ::std::string::String {
+                let mut out = ::std::string::String::from(prefix);
+                match self {
+                    #(#encode_arms)*
+                }
+                out
+            }
+
+            fn decode(prefix: &str, s: &str) -> ::core::option::Option<Self> {
+                let rest = s.strip_prefix(prefix)?.strip_prefix(':')?;
+                let mut parts = rest.split(':');
+                let tag = parts.next()?;
+                let v = match tag {
+                    #(#decode_arms)*
+                    _ => return ::core::option::Option::None,
+                };
+                if parts.next().is_some() {
+                    return ::core::option::Option::None;
+                }
+                ::core::option::Option::Some(v)
+            }
+        }
+    })
+}
+
+fn bindings(v: &Variant) -> Vec<syn::Ident> {
+    (0..v.fields.len()).map(|i| format_ident!("f{i}")).collect()
+}
+
+fn pattern(v: &Variant, binds: &[syn::Ident]) -> TokenStream2 {
+    let ident = &v.ident;
+    match &v.named {
+        Some(names) => quote! { Self::#ident { #(#names: #binds),* } },
+        None if binds.is_empty() => quote! { Self::#ident },
+        None => quote! { Self::#ident(#(#binds),*) },
+    }
+}
+
+fn encode_arm(v: &Variant) -> TokenStream2 {
+    let binds = bindings(v);
+    let pat = pattern(v, &binds);
+    let tag = format!(":{}", v.tag);
+    let pushes = v.fields.iter().zip(&binds).map(|(f, b)| match f.kind {
+        Kind::Int(_) => quote! {
+            out.push(':');
+            out.push_str(&#b.to_string());
+        },
+        Kind::Bool => quote! {
+            out.push(':');
+            out.push(if *#b { '1' } else { '0' });
+        },
+        Kind::Str(max) => quote! {
+            out.push(':');
+            out.push_str(&::telegram_botkit::scene::escape_field(
+                ::telegram_botkit::scene::truncate_field(#b, #max),
+            ));
+        },
+    });
+
+    quote! {
+        #pat => {
+            out.push_str(#tag);
+            #(#pushes)*
+        }
+    }
+}
+
+fn decode_arm(v: &Variant) -> TokenStream2 {
+    let binds = bindings(v);
+    let pat = pattern(v, &binds);
+    let tag = &v.tag;
+    let parses = v.fields.iter().zip(&binds).map(|(f, b)| {
+        let ty = &f.ty;
+        match f.kind {
+            Kind::Int(_) => quote! {
+                let #b: #ty = parts.next()?.parse().ok()?;
+            },
+            Kind::Bool => quote! {
+                let #b: #ty = match parts.next()? {
+                    "1" => true,
+                    "0" => false,
+                    _ => return ::core::option::Option::None,
+                };
+            },
+            Kind::Str(max) => quote! {
+                let #b: #ty = ::telegram_botkit::scene::unescape_field(parts.next()?)?;
+                if #b.len() > #max {
+                    return ::core::option::Option::None;
+                }
+            },
+        }
+    });
+
+    quote! {
+        #tag => {
+            #(#parses)*
+            #pat
+        }
+    }
+}
+
+fn field_kind(f: &syn::Field) -> syn::Result<Kind> {
+    let name = match &f.ty {
+        Type::Path(p) if p.qself.is_none() => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+
+    let kind = match name.as_deref() {
+        Some("u8") => Kind::Int(3),
+        Some("i8") => Kind::Int(4),
+        Some("u16") => Kind::Int(5),
+        Some("i16") => Kind::Int(6),
+        Some("u32") => Kind::Int(10),
+        Some("i32") => Kind::Int(11),
+        Some("u64") | Some("usize") => Kind::Int(20),
+        Some("i64") | Some("isize") => Kind::Int(20),
+        Some("bool") => Kind::Bool,
+        Some("String") => match attr_int(&f.attrs, "max_len")? {
+            Some(n) => Kind::Str(n),
+            None => {
+                return Err(syn::Error::new_spanned(
+                    &f.ty,
+                    "String fields need #[action(max_len = N)]",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &f.ty,
+                "unsupported field type; use integers, bool or String",
+            ));
+        }
+    };
+
+    Ok(kind)
+}
+
+fn attr_str(attrs: &[Attribute], key: &str) -> syn::Result<Option<String>> {
+    let mut out = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("action")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                out = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown action attribute"))
+            }
+        })?;
+    }
+
+    Ok(out)
+}
+
+fn attr_int(attrs: &[Attribute], key: &str) -> syn::Result<Option<usize>> {
+    let mut out = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("action")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                out = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown action attribute"))
+            }
+        })?;
+    }
+
+    Ok(out)
+}
+
+fn snake_case(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 4);
+    for (i, ch) in s.chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(ch.to_lowercase());
+        } else {
+            out.push(ch);
+        }
+    }
+
+    out
+}
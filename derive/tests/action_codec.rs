@@ -0,0 +1,138 @@
+use telegram_botkit::scene::{ActionCodec, CALLBACK_DATA_MAX};
+
+#[derive(ActionCodec, Clone, Debug, PartialEq)]
+pub(crate) enum Event {
+    Refresh,
+    Page(u32),
+    #[action(rename = "amt")]
+    SetAmount {
+        lamports: u64,
+        confirm: bool,
+    },
+    Search(#[action(max_len = 12)] String),
+    Delta(i16, u8),
+}
+
+#[test]
+fn encodes_compactly() {
+    assert_eq!(Event::Refresh.encode("w"), "w:refresh");
+    assert_eq!(Event::Page(3).encode("w"), "w:page:3");
+    assert_eq!(
+        Event::SetAmount {
+            lamports: 5,
+            confirm: true
+        }
+        .encode("w"),
+        "w:amt:5:1"
+    );
+    assert_eq!(Event::Delta(-2, 7).encode("w"), "w:delta:-2:7");
+}
+
+#[test]
+fn roundtrips_every_variant() {
+    let all = [
+        Event::Refresh,
+        Event::Page(u32::MAX),
+        Event::SetAmount {
+            lamports: u64::MAX,
+            confirm: false,
+        },
+        Event::Search("a:b%c ü".into()),
+        Event::Delta(i16::MIN, u8::MAX),
+    ];
+
+    for ev in all {
+        let s = ev.encode("w");
+        assert!(s.is_ascii(), "{s}");
+        assert_eq!(Event::decode("w", &s), Some(ev));
+    }
+}
+
+#[test]
+fn escapes_separators_and_non_ascii() {
+    assert_eq!(
+        Event::Search("a:b%".into()).encode("w"),
+        "w:search:a%3Ab%25"
+    );
+    assert_eq!(Event::Search("ü".into()).encode("w"), "w:search:%C3%BC");
+}
+
+#[test]
+fn truncates_strings_to_max_len() {
+    let s = Event::Search("aaaaaaaaaaaaaaaa".into()).encode("w");
+    assert_eq!(Event::decode("w", &s), Some(Event::Search("a".repeat(12))));
+
+    // 11 ASCII bytes + 2-byte 'ü' would split at 12
+    let s = Event::Search("aaaaaaaaaaaü".into()).encode("w");
+    assert_eq!(Event::decode("w", &s), Some(Event::Search("a".repeat(11))));
+}
+
+#[test]
+fn rejects_foreign_and_malformed_payloads() {
+    assert_eq!(Event::decode("x", "w:refresh"), None);
+    assert_eq!(Event::decode("w", "wx:refresh"), None);
+    assert_eq!(Event::decode("w", "w:page"), None);
+    assert_eq!(Event::decode("w", "w:page:1:2"), None);
+    assert_eq!(Event::decode("w", "w:page:-1"), None);
+    assert_eq!(Event::decode("w", "w:amt:1:2"), None);
+    assert_eq!(Event::decode("w", "w:search:%zz"), None);
+    // Longer than max_len
+    assert_eq!(Event::decode("w", "w:search:aaaaaaaaaaaaa"), None);
+}
+
+#[test]
+fn worst_case_fits_callback_limit() {
+    let max = Event::MAX_ENCODED_LEN.expect("derived");
+    // ":search:" + 12 bytes escaped to 3 each
+    assert_eq!(max, 8 + 36);
+
+    let worst = Event::Search("ü".repeat(6)).encode("w");
+    assert_eq!(worst.len(), 1 + max);
+    assert!(worst.len() <= CALLBACK_DATA_MAX);
+}
+
+mod scenes {
+    use super::Event;
+    use telegram_botkit::scene::*;
+
+    pub(crate) struct LongScene;
+
+    impl Scene for LongScene {
+        const VERSION: u16 = 1;
+        const ID: &'static str = "long";
+        const PREFIX: &'static str = "a_much_longer_prefix_x";
+
+        type State = ();
+        type Event = Event;
+
+        fn init(&self, _ctx: &Ctx) {}
+
+        fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+            unimplemented!()
+        }
+
+        fn bindings(&self) -> Bindings<Event> {
+            Bindings {
+                msg: vec![],
+                cb: vec![],
+            }
+        }
+
+        fn update(
+            &self,
+            _ctx: &Ctx,
+            _s: &Self::State,
+            _e: Self::Event,
+        ) -> Effect<Self::State, Self::Event> {
+            Effect::Noop
+        }
+    }
+}
+
+// Builds evaluate this in const context and
+// fail to compile; here it panics instead.
+#[test]
+#[should_panic(expected = "64-byte callback limit")]
+fn prefix_pushes_events_over_limit() {
+    telegram_botkit::scene::assert_codec_fits::<scenes::LongScene>();
+}
@@ -0,0 +1,8 @@
+use telegram_botkit::scene::ActionCodec;
+
+#[derive(ActionCodec)]
+enum Event {
+    Note(String),
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use telegram_botkit::scene::ActionCodec;
+
+#[derive(ActionCodec)]
+enum Event {
+    Note(#[action(max_len = 24)] String),
+}
+
+fn main() {}
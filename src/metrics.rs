@@ -6,6 +6,8 @@ static ROUTER_COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
 static RESTORE_COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
 static EFFECT_COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
 static VIEW_COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
+static CALLBACK_REJECT_COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
+static THROTTLE_COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
 
 fn meter() -> Meter {
     let scope = InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
@@ -76,3 +78,25 @@ pub fn apply_view(policy: &'static str) {
     });
     c.add(1, &[KeyValue::new("policy", policy)]);
 }
+
+#[inline]
+pub fn callback_rejected(reason: &'static str) {
+    let c = CALLBACK_REJECT_COUNTER.get_or_init(|| {
+        meter()
+            .u64_counter("callback_rejected")
+            .with_description("dropped callback data")
+            .build()
+    });
+    c.add(1, &[KeyValue::new("reason", reason)]);
+}
+
+#[inline]
+pub fn throttled(limit: &'static str) {
+    let c = THROTTLE_COUNTER.get_or_init(|| {
+        meter()
+            .u64_counter("router_throttled")
+            .with_description("updates dropped by rate limits")
+            .build()
+    });
+    c.add(1, &[KeyValue::new("limit", limit)]);
+}
@@ -1,14 +1,23 @@
-use crate::prelude::{UiDialogueStorage, UiStore};
+use crate::prelude::{NavEntry, UiDialogueStorage, UiStore};
 use crate::router;
 use crate::router::AppCtx;
-use crate::router::core::{CbEntryDyn, MsgEntryDyn, init_and_render, run_cb, run_msg};
-use crate::scene::{CbKey, Scene};
-use crate::viewport::{Viewport, store};
+use crate::router::core::{
+    CbEntryDyn, EventDecoder, MsgEntryDyn, MsgRegexes, init_and_render, leave_scene, rerender,
+    resume_and_render, run_cb, run_inline_query, run_msg, run_scheduled,
+};
+use crate::router::deep_link::{DeepLinkDecoder, split_start_param};
+use crate::scene::{ActionCodec, CbKey, MsgPattern, Scene, SceneFuture, assert_codec_fits};
+use crate::ui::callback;
+use crate::viewport::{ScheduledEvent, Viewport, store};
 
 use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use teloxide::dispatching::dialogue::{self, Dialogue};
-use teloxide::types::{CallbackQuery, Message};
+use teloxide::payloads::{AnswerCallbackQuerySetters, SetMyCommandsSetters};
+use teloxide::prelude::Requester;
+use teloxide::types::{
+    BotCommand, BotCommandScope, CallbackQuery, ChatId, InlineQuery, Message, MessageId,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -17,6 +26,80 @@ pub enum ComposeError {
     DuplicateId(&'static str),
     #[error("duplicate scene prefix: {0}")]
     DuplicatePrefix(&'static str),
+    #[error("invalid message regex {0:?}: {1}")]
+    InvalidRegex(&'static str, regex::Error),
+    #[error("command /{0} bound by more than one scene")]
+    DuplicateCommand(&'static str),
+    #[error("invalid command /{0}: use 1-32 of a-z, 0-9 and _")]
+    InvalidCommand(&'static str),
+}
+
+/// Outcome of [`RouterDispatch::switch_to_scene_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Switched {
+    Entered,
+    /// The scene's guard turned the user away and answered them.
+    Denied,
+    /// No scene with that id.
+    Unknown,
+}
+
+/// Outcome of [`RouterDispatch::admit_callback`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Admission {
+    /// Carries the scene the callback was checked against, if any.
+    Admitted(Option<&'static str>),
+    /// The scene's guard turned the user away and answered them.
+    Denied,
+}
+
+/// A `MsgPattern::Command` bound by a scene, see [`Routes::commands`].
+#[derive(Clone, Debug)]
+pub struct CommandInfo {
+    pub command: &'static str,
+    pub scene_id: &'static str,
+    /// `(language, description)`; `None` applies to every language.
+    pub descriptions: Vec<(Option<&'static str>, &'static str)>,
+    pub scope: Option<BotCommandScope>,
+}
+
+impl CommandInfo {
+    fn description(&self, language: Option<&str>) -> Option<&'static str> {
+        let find = |l: Option<&str>| {
+            self.descriptions
+                .iter()
+                .find_map(|(dl, d)| (*dl == l).then_some(*d))
+        };
+
+        find(language).or_else(|| find(None))
+    }
+}
+
+// What `set_my_commands` accepts
+fn valid_command(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+type CommandDescriptions = Vec<(&'static str, Option<&'static str>, &'static str)>;
+
+type GuardFn<C> = Box<router::core::Guard<C>>;
+
+/// Answer to a request a scene guard rejected, see [`SceneBuilder::guard`].
+#[derive(Clone, Debug)]
+pub enum Denied {
+    /// Alert on callbacks, a plain message otherwise.
+    Alert(String),
+    /// Enter this scene instead; its own guard is not checked.
+    Redirect(&'static str),
+}
+
+impl Default for Denied {
+    fn default() -> Self {
+        Self::Alert("You don't have access to this section.".into())
+    }
 }
 
 #[async_trait::async_trait]
@@ -34,6 +117,26 @@ where
         ctx: &C,
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
+    ) -> anyhow::Result<Switched> {
+        self.switch_to_scene_with(id, None, ctx, vp, d).await
+    }
+
+    async fn switch_to_scene_with(
+        &self,
+        id: &str,
+        params: Option<&serde_json::Value>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<Switched>;
+
+    /// Restore and render the last scene from
+    /// the navigation stack; `false` if empty.
+    async fn pop_scene(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
     ) -> anyhow::Result<bool>;
 
     async fn handle_msg(
@@ -51,11 +154,84 @@ where
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
         q: &CallbackQuery,
+    ) -> anyhow::Result<bool> {
+        self.handle_admitted_cb(None, ctx, vp, d, q).await
+    }
+
+    /// `handle_cb` for a callback [`Self::admit_callback`]
+    /// let through; `admitted` isn't guarded a second time.
+    async fn handle_admitted_cb(
+        &self,
+        admitted: Option<&str>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        q: &CallbackQuery,
+    ) -> anyhow::Result<bool>;
+
+    /// Guard of the scene `q` targets, run before the
+    /// callback activates it in the session.
+    async fn admit_callback(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        q: &CallbackQuery,
+    ) -> anyhow::Result<Admission>;
+
+    async fn handle_scheduled(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        ev: &ScheduledEvent,
+    ) -> anyhow::Result<bool>;
+
+    /// First scene answering the query wins.
+    async fn handle_inline_query(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        q: &InlineQuery,
+    ) -> anyhow::Result<bool>;
+
+    /// Render the menu at `source` again as
+    /// scene `id`; `false` if no such scene.
+    async fn rerender(
+        &self,
+        id: &str,
+        source: (ChatId, MessageId),
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<bool>;
+
+    /// Enter the scene a `/start` parameter points to;
+    /// `false` if no scene decodes it.
+    async fn open_deep_link(
+        &self,
+        param: &str,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
     ) -> anyhow::Result<bool>;
 }
 
+/// Context and session types a route set was
+/// composed for; lets the router hold typed layers.
+pub trait RouteTypes {
+    type Ctx;
+    type Session;
+    type Storage;
+}
+
 pub trait SceneLookup: Send + Sync {
     fn find_scene_for_callback(&self, data: &str) -> Option<(&'static str, u16)>;
+
+    /// Cooldown key and window of the binding `q` is dispatched to.
+    fn callback_cooldown(&self, _q: &CallbackQuery) -> Option<(String, std::time::Duration)> {
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -73,8 +249,22 @@ where
 
     fn version(&self) -> u16;
 
+    fn compile(&mut self) -> Result<(), ComposeError>;
+
+    fn deep_link(&self) -> Option<DeepLinkDecoder>;
+
+    fn commands(&self) -> Vec<CommandInfo>;
+
     fn matches_cb(&self, data: &str) -> bool;
 
+    fn cb_cooldown(&self, q: &CallbackQuery) -> Option<(String, std::time::Duration)>;
+
+    fn guarded(&self) -> bool;
+
+    fn denied(&self) -> &Denied;
+
+    async fn allows(&self, ctx: &C) -> bool;
+
     async fn handle_msg(
         &self,
         router: &Routes<C, D, St, M>,
@@ -82,6 +272,7 @@ where
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
         m: &Message,
+        check_guard: bool,
     ) -> anyhow::Result<bool>;
 
     async fn handle_cb(
@@ -93,12 +284,47 @@ where
         q: &CallbackQuery,
     ) -> anyhow::Result<bool>;
 
+    async fn handle_scheduled(
+        &self,
+        router: &Routes<C, D, St, M>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        ev: &ScheduledEvent,
+    ) -> anyhow::Result<bool>;
+
+    async fn handle_inline_query(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        q: &InlineQuery,
+    ) -> anyhow::Result<bool>;
+
     async fn init_and_render(
         &self,
+        params: Option<&serde_json::Value>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<()>;
+
+    async fn resume_and_render(
+        &self,
+        entry: &NavEntry,
         ctx: &C,
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
     ) -> anyhow::Result<()>;
+
+    async fn rerender(
+        &self,
+        source: (ChatId, MessageId),
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<()>;
+
+    async fn leave(&self, ctx: &C, vp: &Viewport<M>, d: &Dialogue<D, St>);
 }
 
 struct SceneRoute<S, C, D, St, M>
@@ -113,6 +339,14 @@ where
     scene: S,
     msg_entry: Option<Box<MsgEntryDyn<S, C, D, St>>>,
     cb_entry: Option<Box<CbEntryDyn<S, C, D, St>>>,
+    decode: Option<EventDecoder<S>>,
+    codec_cooldown: Option<std::time::Duration>,
+    deep_link: Option<DeepLinkDecoder>,
+    descriptions: CommandDescriptions,
+    command_scope: Option<BotCommandScope>,
+    guard: Option<GuardFn<C>>,
+    denied: Denied,
+    regexes: MsgRegexes,
     _pd: PhantomData<(C, D, St, M)>,
 }
 
@@ -138,28 +372,86 @@ where
         S::VERSION
     }
 
+    fn compile(&mut self) -> Result<(), ComposeError> {
+        for b in self.scene.bindings().msg {
+            if let MsgPattern::Regex(src) = b.pattern
+                && !self.regexes.contains_key(src)
+            {
+                let re = regex::Regex::new(src).map_err(|e| ComposeError::InvalidRegex(src, e))?;
+                self.regexes.insert(src, re);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deep_link(&self) -> Option<DeepLinkDecoder> {
+        self.deep_link
+    }
+
+    fn commands(&self) -> Vec<CommandInfo> {
+        let mut out: Vec<CommandInfo> = Vec::new();
+
+        for b in self.scene.bindings().msg {
+            let MsgPattern::Command(name) = b.pattern else {
+                continue;
+            };
+            let name = name.trim_start_matches('/');
+            if out.iter().any(|c| c.command == name) {
+                continue;
+            }
+
+            out.push(CommandInfo {
+                command: name,
+                scene_id: S::ID,
+                descriptions: self
+                    .descriptions
+                    .iter()
+                    .filter(|(c, _, _)| c.trim_start_matches('/') == name)
+                    .map(|(_, lang, d)| (*lang, *d))
+                    .collect(),
+                scope: self.command_scope.clone(),
+            });
+        }
+
+        out
+    }
+
     fn matches_cb(&self, data: &str) -> bool {
         if data.starts_with(S::PREFIX) {
             return true;
         }
 
+        self.scene.bindings().cb.iter().any(|b| b.key.matches(data))
+    }
+
+    fn cb_cooldown(&self, q: &CallbackQuery) -> Option<(String, std::time::Duration)> {
+        // Same pick as `cb_event`: first binding, then the codec
         let bindings = self.scene.bindings();
-        for b in bindings.cb.iter() {
-            match b.key {
-                CbKey::Exact(k) => {
-                    if data == k {
-                        return true;
-                    }
-                }
-                CbKey::Prefix(p) => {
-                    if data.starts_with(p) {
-                        return true;
-                    }
-                }
-            }
+        if let Some(b) = bindings.cb.iter().find(|b| b.event(q).is_some()) {
+            let (CbKey::Exact(key) | CbKey::Prefix(key)) = b.key;
+            return b.cooldown.map(|d| (format!("{}:{key}", S::ID), d));
         }
 
-        false
+        let cooldown = self.codec_cooldown?;
+        self.decode?(S::PREFIX, q.data.as_deref()?)?;
+
+        Some((format!("{}:codec", S::ID), cooldown))
+    }
+
+    fn guarded(&self) -> bool {
+        self.guard.is_some()
+    }
+
+    fn denied(&self) -> &Denied {
+        &self.denied
+    }
+
+    async fn allows(&self, ctx: &C) -> bool {
+        match &self.guard {
+            Some(guard) => guard(ctx).await,
+            None => true,
+        }
     }
 
     async fn handle_msg(
@@ -169,11 +461,14 @@ where
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
         m: &Message,
+        check_guard: bool,
     ) -> anyhow::Result<bool> {
         run_msg(
             &self.scene,
             router,
             self.msg_entry.as_deref(),
+            &self.regexes,
+            self.guard.as_deref().filter(|_| check_guard),
             ctx,
             vp,
             d,
@@ -190,16 +485,71 @@ where
         d: &Dialogue<D, St>,
         q: &CallbackQuery,
     ) -> anyhow::Result<bool> {
-        run_cb(&self.scene, router, self.cb_entry.as_deref(), ctx, vp, d, q).await
+        run_cb(
+            &self.scene,
+            router,
+            self.cb_entry.as_deref(),
+            self.decode,
+            ctx,
+            vp,
+            d,
+            q,
+        )
+        .await
+    }
+
+    async fn handle_scheduled(
+        &self,
+        router: &Routes<C, D, St, M>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        ev: &ScheduledEvent,
+    ) -> anyhow::Result<bool> {
+        run_scheduled(&self.scene, router, self.decode, ctx, vp, d, ev).await
+    }
+
+    async fn handle_inline_query(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        q: &InlineQuery,
+    ) -> anyhow::Result<bool> {
+        run_inline_query(&self.scene, ctx, vp, q).await
     }
 
     async fn init_and_render(
         &self,
+        params: Option<&serde_json::Value>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<()> {
+        init_and_render(&self.scene, params, ctx, vp, d).await
+    }
+
+    async fn resume_and_render(
+        &self,
+        entry: &NavEntry,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<()> {
+        resume_and_render(&self.scene, entry, ctx, vp, d).await
+    }
+
+    async fn rerender(
+        &self,
+        source: (ChatId, MessageId),
         ctx: &C,
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
     ) -> anyhow::Result<()> {
-        init_and_render(&self.scene, ctx, vp, d).await
+        rerender(&self.scene, source, ctx, vp, d).await
+    }
+
+    async fn leave(&self, ctx: &C, vp: &Viewport<M>, d: &Dialogue<D, St>) {
+        leave_scene(&self.scene, ctx, vp, d).await
     }
 }
 
@@ -211,6 +561,7 @@ where
     items: Vec<Box<dyn RouteFns<C, D, St, M>>>,
     idx_by_id: HashMap<&'static str, usize>,
     prefixes: Vec<(&'static str, usize)>,
+    commands: Vec<CommandInfo>,
 }
 
 impl<C, D, St, M> Routes<C, D, St, M>
@@ -221,13 +572,16 @@ where
     <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
     M: store::Store + Send + Sync,
 {
-    fn new(items: Vec<Box<dyn RouteFns<C, D, St, M>>>) -> Result<Self, ComposeError> {
+    fn new(mut items: Vec<Box<dyn RouteFns<C, D, St, M>>>) -> Result<Self, ComposeError> {
         let mut idx_by_id = HashMap::new();
         let mut seen_ids = HashSet::new();
         let mut seen_prefix = HashSet::new();
         let mut prefixes = Vec::with_capacity(items.len());
+        let mut commands: Vec<CommandInfo> = Vec::new();
+
+        for (i, it) in items.iter_mut().enumerate() {
+            it.compile()?;
 
-        for (i, it) in items.iter().enumerate() {
             let id = it.id();
             if !seen_ids.insert(id) {
                 return Err(ComposeError::DuplicateId(id));
@@ -241,14 +595,168 @@ where
             }
 
             prefixes.push((pf, i));
+
+            for cmd in it.commands() {
+                if !valid_command(cmd.command) {
+                    return Err(ComposeError::InvalidCommand(cmd.command));
+                }
+                if commands.iter().any(|c| c.command == cmd.command) {
+                    return Err(ComposeError::DuplicateCommand(cmd.command));
+                }
+
+                commands.push(cmd);
+            }
         }
 
         Ok(Self {
             items,
             idx_by_id,
             prefixes,
+            commands,
         })
     }
+
+    /// Commands bound by the routed scenes, in route order.
+    pub fn commands(&self) -> &[CommandInfo] {
+        &self.commands
+    }
+
+    /// Replace the bot's command lists with [`Self::commands`]: one
+    /// `set_my_commands` call per scope and language. A scoped list
+    /// replaces the default one, so unscoped commands are repeated in
+    /// it. Commands without a description are left out.
+    pub async fn publish_commands<R: Requester>(&self, bot: &R) -> Result<(), R::Err> {
+        let mut scopes: Vec<Option<&BotCommandScope>> = Vec::new();
+        let mut languages: Vec<Option<&'static str>> = vec![None];
+
+        for c in &self.commands {
+            if !scopes.contains(&c.scope.as_ref()) {
+                scopes.push(c.scope.as_ref());
+            }
+            for (lang, _) in &c.descriptions {
+                if !languages.contains(lang) {
+                    languages.push(*lang);
+                }
+            }
+        }
+
+        for scope in scopes {
+            for lang in &languages {
+                let mut own = false;
+                let list: Vec<BotCommand> = self
+                    .commands
+                    .iter()
+                    .filter(|c| c.scope.is_none() || c.scope.as_ref() == scope)
+                    .filter_map(|c| {
+                        let cmd = BotCommand::new(c.command, c.description(*lang)?);
+                        own |= c.scope.as_ref() == scope;
+                        Some(cmd)
+                    })
+                    .collect();
+                // A scope with nothing of its own would repeat the default list
+                if !own {
+                    continue;
+                }
+
+                let mut req = bot.set_my_commands(list);
+                if let Some(scope) = scope {
+                    req = req.scope(scope.clone());
+                }
+                if let Some(lang) = lang {
+                    req = req.language_code(*lang);
+                }
+
+                req.await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Route of the active scene unless it
+    // is the one being (re)entered.
+    async fn leaving(&self, d: &Dialogue<D, St>, next: &str) -> Option<usize> {
+        let active = d.get().await.ok().flatten()?.ui_get_active_scene_id()?;
+        if active == next {
+            return None;
+        }
+
+        self.idx_by_id.get(active.as_str()).copied()
+    }
+
+    async fn enter(
+        &self,
+        i: usize,
+        params: Option<&serde_json::Value>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<()> {
+        if let Some(l) = self.leaving(d, self.items[i].id()).await {
+            self.items[l].leave(ctx, vp, d).await;
+        }
+
+        self.items[i].init_and_render(params, ctx, vp, d).await
+    }
+
+    // Runs the guard of route `i`; a rejected request is
+    // answered here and reported as `false`.
+    async fn admit(
+        &self,
+        i: usize,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        q: Option<&CallbackQuery>,
+    ) -> anyhow::Result<bool> {
+        let it = &self.items[i];
+        if !it.guarded() || it.allows(ctx).await {
+            return Ok(true);
+        }
+
+        tracing::debug!(
+            chat=%ctx.chat().0,
+            user=%ctx.user_id(),
+            scene_id=%it.id(),
+            "scene guard denied"
+        );
+
+        match it.denied() {
+            Denied::Alert(text) => {
+                let res = match q {
+                    Some(q) => ctx
+                        .bot()
+                        .answer_callback_query(q.id.clone())
+                        .text(text.clone())
+                        .show_alert(true)
+                        .await
+                        .map(drop),
+                    None => ctx
+                        .bot()
+                        .send_message(ctx.chat(), text.clone())
+                        .await
+                        .map(drop),
+                };
+                if let Err(e) = res {
+                    tracing::warn!(error=?e, "denied alert failed");
+                }
+            }
+            Denied::Redirect(id) => {
+                if let Some(q) = q
+                    && let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await
+                {
+                    tracing::warn!(error=?e, "answer_callback_query failed (denied)");
+                }
+
+                match self.idx_by_id.get(id).copied() {
+                    Some(j) => self.enter(j, None, ctx, vp, d).await?,
+                    None => tracing::warn!(to=%id, "denied redirect to unknown scene"),
+                }
+            }
+        }
+
+        Ok(false)
+    }
 }
 
 #[async_trait::async_trait]
@@ -260,20 +768,63 @@ where
     <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
     M: store::Store + Send + Sync,
 {
-    async fn switch_to_scene_by_id(
+    async fn switch_to_scene_with(
         &self,
         id: &str,
+        params: Option<&serde_json::Value>,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<Switched> {
+        let Some(i) = self.idx_by_id.get(id).copied() else {
+            return Ok(Switched::Unknown);
+        };
+
+        if !self.admit(i, ctx, vp, d, None).await? {
+            return Ok(Switched::Denied);
+        }
+
+        self.enter(i, params, ctx, vp, d).await?;
+        Ok(Switched::Entered)
+    }
+
+    async fn pop_scene(
+        &self,
         ctx: &C,
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
     ) -> anyhow::Result<bool> {
-        match self.idx_by_id.get(id).copied() {
-            Some(i) => {
-                self.items[i].init_and_render(ctx, vp, d).await?;
-                Ok(true)
-            }
-            None => Ok(false),
+        let Ok(mut s) = d.get_or_default().await else {
+            return Ok(false);
+        };
+        let Some(entry) = s.ui_pop_nav_entry() else {
+            return Ok(false);
+        };
+        let leaving = self.leaving(d, &entry.scene_id).await;
+
+        if let Err(e) = d.update(s).await {
+            tracing::error!(
+                error=?e,
+                chat=%ctx.chat().0,
+                "dialogue update failed (pop_scene)"
+            );
+        }
+
+        let Some(i) = self.idx_by_id.get(entry.scene_id.as_str()).copied() else {
+            return Ok(false);
+        };
+
+        // Access may have been revoked since the push
+        if !self.admit(i, ctx, vp, d, None).await? {
+            return Ok(true);
+        }
+
+        if let Some(l) = leaving {
+            self.items[l].leave(ctx, vp, d).await;
         }
+
+        self.items[i].resume_and_render(&entry, ctx, vp, d).await?;
+        Ok(true)
     }
 
     async fn handle_msg(
@@ -284,14 +835,19 @@ where
         d: &Dialogue<D, St>,
         m: &Message,
     ) -> anyhow::Result<bool> {
-        if let Some(id) = active {
-            if let Some(i) = self.idx_by_id.get(id).copied() {
-                return self.items[i].handle_msg(self, ctx, vp, d, m).await;
+        if let Some(id) = active
+            && let Some(i) = self.idx_by_id.get(id).copied()
+        {
+            if !self.admit(i, ctx, vp, d, None).await? {
+                return Ok(true);
             }
+
+            return self.items[i].handle_msg(self, ctx, vp, d, m, false).await;
         }
 
+        // Guarded scenes only get messages meant for them
         for it in &self.items {
-            if it.handle_msg(self, ctx, vp, d, m).await? {
+            if it.handle_msg(self, ctx, vp, d, m, true).await? {
                 return Ok(true);
             }
         }
@@ -299,8 +855,9 @@ where
         Ok(false)
     }
 
-    async fn handle_cb(
+    async fn handle_admitted_cb(
         &self,
+        admitted: Option<&str>,
         ctx: &C,
         vp: &Viewport<M>,
         d: &Dialogue<D, St>,
@@ -308,13 +865,21 @@ where
     ) -> anyhow::Result<bool> {
         let data = q.data.as_deref().unwrap_or("");
 
-        if let Some((id, _ver)) = self.find_scene_for_callback(data) {
-            if let Some(i) = self.idx_by_id.get(id).copied() {
-                return self.items[i].handle_cb(self, ctx, vp, d, q).await;
+        if let Some((id, _ver)) = self.find_scene_for_callback(data)
+            && let Some(i) = self.idx_by_id.get(id).copied()
+        {
+            if admitted != Some(id) && !self.admit(i, ctx, vp, d, Some(q)).await? {
+                return Ok(true);
             }
+
+            return self.items[i].handle_cb(self, ctx, vp, d, q).await;
         }
 
         for it in &self.items {
+            if it.guarded() && admitted != Some(it.id()) && !it.allows(ctx).await {
+                continue;
+            }
+
             if it.handle_cb(self, ctx, vp, d, q).await? {
                 return Ok(true);
             }
@@ -322,6 +887,141 @@ where
 
         Ok(false)
     }
+
+    async fn admit_callback(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        q: &CallbackQuery,
+    ) -> anyhow::Result<Admission> {
+        let data = q.data.as_deref().unwrap_or("");
+        // Built-ins are checked where they lead, see `pop_scene`
+        if data.starts_with(callback::UI_PREFIX) {
+            return Ok(Admission::Admitted(None));
+        }
+
+        // Same pick as `Viewport::activate_from_callback`
+        let meta = match &q.message {
+            Some(msg) => vp.load_meta(msg.chat().id, msg.id().0).await.ok().flatten(),
+            None => None,
+        };
+        let id = match &meta {
+            Some(meta) => Some(meta.scene_id.as_str()),
+            None => self.find_scene_for_callback(data).map(|(id, _)| id),
+        };
+        let Some(i) = id.and_then(|id| self.idx_by_id.get(id).copied()) else {
+            return Ok(Admission::Admitted(None));
+        };
+
+        Ok(match self.admit(i, ctx, vp, d, Some(q)).await? {
+            true => Admission::Admitted(Some(self.items[i].id())),
+            false => Admission::Denied,
+        })
+    }
+
+    async fn handle_scheduled(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+        ev: &ScheduledEvent,
+    ) -> anyhow::Result<bool> {
+        let Some(it) = self
+            .idx_by_id
+            .get(ev.scene_id.as_str())
+            .map(|i| &self.items[*i])
+        else {
+            return Ok(false);
+        };
+
+        // Nobody is waiting on it, so drop it quietly
+        if it.guarded() && !it.allows(ctx).await {
+            tracing::debug!(scene_id=%it.id(), "scheduled event denied by guard");
+            return Ok(false);
+        }
+
+        it.handle_scheduled(self, ctx, vp, d, ev).await
+    }
+
+    async fn handle_inline_query(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        q: &InlineQuery,
+    ) -> anyhow::Result<bool> {
+        for it in &self.items {
+            if it.guarded() && !it.allows(ctx).await {
+                continue;
+            }
+
+            if it.handle_inline_query(ctx, vp, q).await? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn rerender(
+        &self,
+        id: &str,
+        source: (ChatId, MessageId),
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<bool> {
+        let Some(i) = self.idx_by_id.get(id).copied() else {
+            return Ok(false);
+        };
+
+        if self.admit(i, ctx, vp, d, None).await? {
+            self.items[i].rerender(source, ctx, vp, d).await?;
+        }
+
+        Ok(true)
+    }
+
+    async fn open_deep_link(
+        &self,
+        param: &str,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, St>,
+    ) -> anyhow::Result<bool> {
+        let Some((prefix, payload)) = split_start_param(param) else {
+            return Ok(false);
+        };
+        let Some(i) = self
+            .prefixes
+            .iter()
+            .find_map(|(pf, i)| (*pf == prefix).then_some(*i))
+        else {
+            return Ok(false);
+        };
+        let Some(decode) = self.items[i].deep_link() else {
+            return Ok(false);
+        };
+        let Some(params) = decode(payload) else {
+            tracing::debug!(prefix, "deep link payload rejected");
+            return Ok(false);
+        };
+
+        let switched = self
+            .switch_to_scene_with(self.items[i].id(), Some(&params), ctx, vp, d)
+            .await?;
+        Ok(switched != Switched::Unknown)
+    }
+}
+
+impl<C, D, St, M> RouteTypes for Routes<C, D, St, M>
+where
+    C: AppCtx + Send + Sync,
+    D: UiStore + Send + Sync,
+{
+    type Ctx = C;
+    type Session = D;
+    type Storage = St;
 }
 
 impl<C, D, St, M> SceneLookup for Routes<C, D, St, M>
@@ -353,6 +1053,12 @@ where
 
         None
     }
+
+    fn callback_cooldown(&self, q: &CallbackQuery) -> Option<(String, std::time::Duration)> {
+        let (id, _) = self.find_scene_for_callback(q.data.as_deref()?)?;
+
+        self.items[*self.idx_by_id.get(id)?].cb_cooldown(q)
+    }
 }
 
 pub struct Builder<C, D, St, M>
@@ -383,6 +1089,14 @@ where
             scene: sc.scene,
             msg_entry: sc.msg_entry,
             cb_entry: sc.cb_entry,
+            decode: sc.decode,
+            codec_cooldown: sc.codec_cooldown,
+            deep_link: sc.deep_link,
+            descriptions: sc.descriptions,
+            command_scope: sc.command_scope,
+            guard: sc.guard,
+            denied: sc.denied,
+            regexes: MsgRegexes::new(),
             _pd: PhantomData,
         });
 
@@ -417,6 +1131,13 @@ where
     scene: S,
     msg_entry: Option<Box<MsgEntryDyn<S, C, D, St>>>,
     cb_entry: Option<Box<CbEntryDyn<S, C, D, St>>>,
+    decode: Option<EventDecoder<S>>,
+    codec_cooldown: Option<std::time::Duration>,
+    deep_link: Option<DeepLinkDecoder>,
+    descriptions: CommandDescriptions,
+    command_scope: Option<BotCommandScope>,
+    guard: Option<GuardFn<C>>,
+    denied: Denied,
     _pd: PhantomData<(C, D, St, M)>,
 }
 
@@ -459,6 +1180,80 @@ where
         self.cb_entry = Some(Box::new(f));
         self
     }
+
+    /// Open the scene from `/start {PREFIX}-{payload}` links made
+    /// by `deep_link::start_link`; the params reach `Scene::init_with`.
+    pub fn deep_link(self) -> Self {
+        self.deep_link_with(crate::router::deep_link::decode_json)
+    }
+
+    /// Like [`Self::deep_link`] with a custom payload format.
+    pub fn deep_link_with(mut self, decode: DeepLinkDecoder) -> Self {
+        self.deep_link = Some(decode);
+        self
+    }
+
+    /// Description of a bound command for `Routes::publish_commands`.
+    pub fn describe(mut self, command: &'static str, description: &'static str) -> Self {
+        self.descriptions.push((command, None, description));
+        self
+    }
+
+    pub fn describe_in(
+        mut self,
+        command: &'static str,
+        language: &'static str,
+        description: &'static str,
+    ) -> Self {
+        self.descriptions
+            .push((command, Some(language), description));
+        self
+    }
+
+    /// Publish this scene's commands only in `scope`.
+    pub fn command_scope(mut self, scope: BotCommandScope) -> Self {
+        self.command_scope = Some(scope);
+        self
+    }
+
+    /// Access check run before messages, callbacks and switches reach
+    /// the scene; rejected requests get [`Self::on_denied`].
+    pub fn guard<F>(mut self, f: F) -> Self
+    where
+        for<'a> F: Fn(&'a C) -> SceneFuture<'a, bool> + Send + Sync + 'static,
+    {
+        self.guard = Some(Box::new(f));
+        self
+    }
+
+    /// Defaults to an "access denied" alert.
+    pub fn on_denied(mut self, denied: Denied) -> Self {
+        self.denied = denied;
+        self
+    }
+}
+
+impl<S, C, D, St, M> SceneBuilder<S, C, D, St, M>
+where
+    S: Scene,
+    S::Event: ActionCodec,
+    C: AppCtx,
+{
+    /// Decode callbacks and scheduled events with the
+    /// scene's `ActionCodec`; no `bindings()` needed.
+    pub fn codec(mut self) -> Self {
+        const { assert_codec_fits::<S>() };
+
+        self.decode = Some(<S::Event as ActionCodec>::decode);
+        self
+    }
+
+    /// Like `CbBinding::cooldown` for callbacks decoded
+    /// by [`Self::codec`], shared by all of its events.
+    pub fn codec_cooldown(mut self, cooldown: std::time::Duration) -> Self {
+        self.codec_cooldown = Some(cooldown);
+        self
+    }
 }
 
 pub fn scene<S, C, D, St, M>() -> SceneBuilder<S, C, D, St, M>
@@ -473,6 +1268,13 @@ where
         scene: S::default(),
         msg_entry: None,
         cb_entry: None,
+        decode: None,
+        codec_cooldown: None,
+        deep_link: None,
+        descriptions: Vec::new(),
+        command_scope: None,
+        guard: None,
+        denied: Denied::default(),
         _pd: PhantomData,
     }
 }
@@ -489,6 +1291,13 @@ where
         scene,
         msg_entry: None,
         cb_entry: None,
+        decode: None,
+        codec_cooldown: None,
+        deep_link: None,
+        descriptions: Vec::new(),
+        command_scope: None,
+        guard: None,
+        denied: Denied::default(),
         _pd: PhantomData,
     }
 }
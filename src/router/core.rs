@@ -1,32 +1,55 @@
 use crate::router::compose;
-use crate::scene::{Ctx as SceneCtx, Effect, MsgPattern, RenderPolicy, Scene, UiEffect};
-use crate::session::{UiDialogueStorage, UiStore};
+use crate::scene::{
+    ChatKind, Ctx as SceneCtx, Effect, MsgMatch, MsgPattern, RenderPolicy, Scene, SceneFuture,
+    UiEffect,
+};
+use crate::session::{NavEntry, UiDialogueStorage, UiStore};
 use crate::ui::{callback, message, prelude::UiRequester};
-use crate::viewport::{MetaSpec, SNAP_TTL_SECS, Viewport, store};
+use crate::viewport::{MetaSpec, SNAP_TTL_SECS, ScheduledEvent, Viewport, store, unix_now};
 
 use super::AppCtx;
 
 use dialogue::Dialogue;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use teloxide::dispatching::dialogue;
+use teloxide::payloads::AnswerInlineQuerySetters;
 use teloxide::prelude::Requester;
 use teloxide::types::{
-    CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId,
-    ParseMode,
+    CallbackQuery, Chat, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery, Message,
+    MessageId, ParseMode, User,
 };
 use tracing::instrument;
 
 pub const DIALOGUE_SNAPSHOT_TAG: &str = "tgk:s1";
 
+/// `MsgPattern::Regex` sources compiled at build time.
+pub type MsgRegexes = HashMap<&'static str, Regex>;
+
+/// Scene guard, see `SceneBuilder::guard`.
+pub type Guard<C> = dyn for<'a> Fn(&'a C) -> SceneFuture<'a, bool> + Send + Sync;
+
+async fn allowed<C>(guard: Option<&Guard<C>>, ctx: &C) -> bool {
+    match guard {
+        Some(guard) => guard(ctx).await,
+        None => true,
+    }
+}
+
 #[derive(serde::Deserialize)]
 struct DialogueSnapshot<'a> {
     #[serde(rename = "_tgk")]
     tag: &'a str,
+    // Cow: escaped JSON bodies can't be borrowed
     #[serde(borrow)]
-    state: &'a str,
+    state: Cow<'a, str>,
     #[serde(borrow)]
-    checksum: Option<&'a str>,
+    checksum: Option<Cow<'a, str>>,
+    #[serde(default)]
+    version: Option<u16>,
 }
 
 pub type EntryFuture<'a, S> =
@@ -48,6 +71,8 @@ pub type MsgEntryDyn<S, C, D, St> = dyn for<'a> Fn(
     + Send
     + Sync;
 
+pub type EventDecoder<S> = fn(&str, &str) -> Option<<S as Scene>::Event>;
+
 pub type CbEntryDyn<S, C, D, St> = dyn for<'a> Fn(
         &'a <C as AppCtx>::Bot,
         &'a Dialogue<D, St>,
@@ -57,20 +82,51 @@ pub type CbEntryDyn<S, C, D, St> = dyn for<'a> Fn(
     + Send
     + Sync;
 
+/// Scene context for an event; `from` and
+/// `chat` take precedence over [`AppCtx`].
+pub fn scene_ctx<C: AppCtx>(ctx: &C, from: Option<&User>, chat: Option<&Chat>) -> SceneCtx {
+    let username = from
+        .and_then(|u| u.username.clone())
+        .or_else(|| ctx.username().map(str::to_owned));
+    let language_code = from
+        .and_then(|u| u.language_code.clone())
+        .or_else(|| ctx.language_code().map(str::to_owned));
+
+    SceneCtx {
+        user_id: ctx.user_id(),
+        chat_id: chat.map(|c| c.id.0).unwrap_or(ctx.chat().0),
+        chat_kind: chat.map(ChatKind::of).unwrap_or(ctx.chat_kind()),
+        language_code,
+        username,
+        now: unix_now(),
+    }
+}
+
 #[instrument(
     name = "router.ui_effects",
-    skip(bot, d, ui),
+    skip(bot, vp, d, ui),
     fields(chat_id = %chat.0, effects = %ui.len())
 )]
-pub async fn run_ui_effects<R, D, S>(bot: &R, chat: ChatId, d: &Dialogue<D, S>, ui: &Vec<UiEffect>)
-where
+pub async fn run_ui_effects<R, D, S, M>(
+    bot: &R,
+    vp: &Viewport<M>,
+    chat: ChatId,
+    d: &Dialogue<D, S>,
+    ui: &Vec<UiEffect>,
+) where
     R: UiRequester,
     <R as Requester>::SendMessage: Send,
     <R as Requester>::DeleteMessage: Send,
     D: UiStore + Send + Sync,
     S: UiDialogueStorage<D>,
     <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
 {
+    if !ui.is_empty() && vp.is_unreachable(chat).await {
+        tracing::debug!(chat=%chat.0, "ui effects skipped, chat unreachable");
+        return;
+    }
+
     for eff in ui {
         match eff {
             UiEffect::Notification { text_md, ttl_secs } => {
@@ -92,7 +148,7 @@ where
                     None,
                     text,
                     message::ReplyOptions {
-                        markup: Some(kb),
+                        markup: Some(kb.into()),
                         parse_mode: Some(ParseMode::MarkdownV2),
                         disable_web_page_preview: Some(false),
                     },
@@ -103,12 +159,66 @@ where
                     Ok(_) => {}
                     Err(e) => {
                         tracing::warn!(error=?e, chat=%chat.0, "notification send failed");
+
+                        if message::is_unreachable_error(&e) {
+                            vp.set_unreachable(chat, true).await;
+                        }
                     }
                 }
             }
             UiEffect::ClearPrompt => {
                 message::clear_input_prompt_message(bot, chat, d).await;
             }
+            UiEffect::Schedule { .. } => {
+                // Needs the viewport store, see schedule_events
+            }
+        }
+    }
+}
+
+#[instrument(
+    name = "router.schedule_events",
+    skip(vp, d, ui),
+    fields(scene_id = %S::ID, chat_id = %chat.0)
+)]
+pub async fn schedule_events<S, D, St, M>(
+    vp: &Viewport<M>,
+    chat: ChatId,
+    user_id: i64,
+    d: &Dialogue<D, St>,
+    ui: &[UiEffect],
+) where
+    S: Scene,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+{
+    for eff in ui {
+        let UiEffect::Schedule {
+            delay_secs,
+            payload,
+        } = eff
+        else {
+            continue;
+        };
+
+        let target = d
+            .get()
+            .await
+            .ok()
+            .flatten()
+            .and_then(|s| s.ui_get_last_action_message_id());
+        let Some(mid) = target else {
+            tracing::warn!(chat=%chat.0, "no message to schedule event for");
+            continue;
+        };
+
+        if let Err(e) = vp
+            .schedule_event(chat, user_id, mid, S::ID, payload.clone(), *delay_secs)
+            .await
+        {
+            tracing::error!(error=?e, chat=%chat.0, mid=%mid, "schedule event failed");
         }
     }
 }
@@ -119,7 +229,8 @@ where
     fields(
         scene_id = %S::ID,
         source_mid = source.as_ref().map(|(_, m)| m.0).unwrap_or_default(),
-        restore_path = tracing::field::Empty
+        restore_path = tracing::field::Empty,
+        from_version = tracing::field::Empty
     )
 )]
 pub async fn restore_state<S: Scene, D, St, M>(
@@ -139,54 +250,66 @@ where
     let mut label: &'static str = "init";
 
     if let Some((chat_id, mid)) = source {
-        if let Ok(Some(sess)) = d.get().await {
-            if sess.ui_get_last_action_message_id() == Some(mid.0) {
-                if let Some(json) = sess.ui_get_scene_for_message(mid.0) {
-                    let (state_json, state_checksum) =
-                        if let Ok(env) = serde_json::from_str::<DialogueSnapshot>(&json) {
-                            if env.tag == DIALOGUE_SNAPSHOT_TAG {
-                                (Some(env.state), env.checksum)
-                            } else {
-                                (Some(json.as_str()), None)
-                            }
-                        } else {
-                            (Some(json.as_str()), None)
-                        };
-
-                    if let Some(body) = state_json {
-                        let snap = crate::scene::Snapshot {
-                            scene_id: S::ID,
-                            scene_version: S::VERSION,
-                            state_json: Some(body),
-                            state_checksum,
-                        };
-
-                        if let Some(st) = scene.restore(snap) {
-                            out_state = Some(st);
-                            label = "dialogue";
-                        }
+        if let Ok(Some(sess)) = d.get().await
+            && sess.ui_get_last_action_message_id() == Some(mid.0)
+            && let Some(json) = sess.ui_get_scene_for_message(mid.0)
+        {
+            let (state_json, state_checksum, version) =
+                if let Ok(env) = serde_json::from_str::<DialogueSnapshot>(&json) {
+                    if env.tag == DIALOGUE_SNAPSHOT_TAG {
+                        (Some(env.state), env.checksum, env.version)
+                    } else {
+                        (Some(Cow::Borrowed(json.as_str())), None, None)
                     }
-                }
-            }
-        }
+                } else {
+                    (Some(Cow::Borrowed(json.as_str())), None, None)
+                };
 
-        if out_state.is_none() {
-            if let Ok(Some(meta)) = vp.load_meta(chat_id, mid.0).await {
+            if let Some(body) = state_json {
+                // Envelopes written before versioning
+                // are assumed to match the current scene.
+                let version = version.unwrap_or(S::VERSION);
                 let snap = crate::scene::Snapshot {
-                    scene_id: &meta.scene_id,
-                    scene_version: meta.scene_version,
-                    state_json: meta.state_json.as_deref(),
-                    state_checksum: meta.state_checksum.as_deref(),
+                    scene_id: S::ID,
+                    scene_version: version,
+                    state_json: Some(&body),
+                    state_checksum: state_checksum.as_deref(),
                 };
 
                 if let Some(st) = scene.restore(snap) {
                     out_state = Some(st);
-                    label = "meta";
-                } else {
-                    label = "mismatch";
+                    label = if version == S::VERSION {
+                        "dialogue"
+                    } else {
+                        tracing::Span::current().record("from_version", version);
+                        "migrated"
+                    };
                 }
             }
         }
+
+        if out_state.is_none()
+            && let Ok(Some(meta)) = vp.load_meta(chat_id, mid.0).await
+        {
+            let snap = crate::scene::Snapshot {
+                scene_id: &meta.scene_id,
+                scene_version: meta.scene_version,
+                state_json: meta.state_json.as_deref(),
+                state_checksum: meta.state_checksum.as_deref(),
+            };
+
+            if let Some(st) = scene.restore(snap) {
+                out_state = Some(st);
+                label = if meta.scene_version == S::VERSION {
+                    "meta"
+                } else {
+                    tracing::Span::current().record("from_version", meta.scene_version);
+                    "migrated"
+                };
+            } else {
+                label = "mismatch";
+            }
+        }
     }
 
     let state = out_state.unwrap_or_else(|| scene.init(sctx));
@@ -219,7 +342,7 @@ pub async fn apply_effect<S, C, D, St, M, R>(
     vp: &Viewport<M>,
     d: &Dialogue<D, St>,
     sctx: &SceneCtx,
-    eff: Effect<S::State>,
+    eff: Effect<S::State, S::Event>,
 ) -> anyhow::Result<()>
 where
     S: Scene,
@@ -230,138 +353,151 @@ where
     M: store::Store + Send + Sync,
     R: compose::RouterDispatch<C, D, St, M>,
 {
-    let eff_label: &str = match &eff {
-        Effect::Stay(_, _) => "Stay",
-        Effect::StayWithEffect(_, _, _) => "StayWithEffect",
-        Effect::SwitchScene(_) => "SwitchScene",
-        Effect::Noop => "Noop",
-        Effect::NoopWithEffect(_) => "NoopWithEffect",
-    };
-    tracing::Span::current().record("effect", eff_label);
+    let mut eff = eff;
 
-    #[cfg(feature = "metrics")]
-    {
-        crate::metrics::apply_effect(S::ID, eff_label);
-    }
+    loop {
+        let eff_label: &str = match &eff {
+            Effect::Stay(_, _) => "Stay",
+            Effect::StayWithEffect(_, _, _) => "StayWithEffect",
+            Effect::StayWithCmd(_, _, _) => "StayWithCmd",
+            Effect::SwitchScene(_) => "SwitchScene",
+            Effect::Push(_, _) => "Push",
+            Effect::Pop => "Pop",
+            Effect::Noop => "Noop",
+            Effect::NoopWithEffect(_) => "NoopWithEffect",
+        };
+        tracing::Span::current().record("effect", eff_label);
 
-    match eff {
-        Effect::Stay(ns, pol) => {
-            let view = scene.render(sctx, &ns);
-            let snap = scene.snapshot(&ns);
-
-            if let Ok(mut s) = d.get_or_default().await {
-                s.ui_set_active_scene_id(Some(S::ID.to_string()));
+        #[cfg(feature = "metrics")]
+        {
+            crate::metrics::apply_effect(S::ID, eff_label);
+        }
 
-                if let Err(e) = d.update(s).await {
-                    tracing::error!(
-                        error=?e,
-                        chat=%ctx.chat().0,
-                        scene_id=%S::ID,
-                        "dialogue update failed (apply_effect:Stay)"
-                    );
-                }
+        match eff {
+            Effect::Stay(ns, pol) => {
+                render_stay(scene, ctx, vp, d, sctx, ns, pol, "Stay").await?;
+            }
+            Effect::StayWithEffect(ns, pol, ui) => {
+                render_stay(scene, ctx, vp, d, sctx, ns, pol, "StayWithEffect").await?;
+                run_ui_effects(ctx.bot(), vp, ctx.chat(), d, &ui).await;
+                schedule_events::<S, _, _, _>(vp, ctx.chat(), ctx.user_id(), d, &ui).await;
             }
+            Effect::StayWithCmd(ns, pol, cmd) => {
+                render_stay(scene, ctx, vp, d, sctx, ns, pol, "StayWithCmd").await?;
 
-            vp.apply_view(
-                ctx.bot(),
-                ctx.chat(),
-                d,
-                &view,
-                pol,
-                Some(MetaSpec {
-                    scene_id: S::ID,
-                    scene_version: S::VERSION,
-                    state_json: snap.0,
-                    state_ref: snap.1,
-                    ttl_secs: SNAP_TTL_SECS,
-                }),
-            )
-            .await?;
-        }
-        Effect::StayWithEffect(ns, pol, ui) => {
-            let view = scene.render(sctx, &ns);
-            let snap = scene.snapshot(&ns);
+                let ev = cmd.run().await;
 
-            if let Ok(mut s) = d.get_or_default().await {
-                s.ui_set_active_scene_id(Some(S::ID.to_string()));
+                let source = d
+                    .get()
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|s| s.ui_get_last_action_message_id())
+                    .map(|mid| (ctx.chat(), MessageId(mid)));
+                let (state, _rpath) = restore_state(scene, vp, d, sctx, source).await;
 
-                if let Err(e) = d.update(s).await {
-                    tracing::error!(
-                        error=?e,
-                        chat=%ctx.chat().0,
-                        scene_id=%S::ID,
-                        "dialogue update failed (apply_effect:StayWithEffect)"
-                    );
+                // Loop instead of recursing
+                // to keep the future Send.
+                eff = scene.update_async(sctx, state, ev).await;
+                continue;
+            }
+            Effect::SwitchScene(sw) => {
+                match routes
+                    .switch_to_scene_with(sw.to_scene_id, sw.params.as_ref(), ctx, vp, d)
+                    .await
+                {
+                    Ok(compose::Switched::Unknown) => {
+                        tracing::warn!(
+                            chat=%ctx.chat().0,
+                            scene_id=%S::ID,
+                            to=%sw.to_scene_id,
+                            "switch_to_scene_with: unknown scene"
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::error!(
+                            error=?e,
+                            chat=%ctx.chat().0,
+                            to=%sw.to_scene_id,
+                            "switch_to_scene_with failed",
+                        );
+
+                        return Err(e);
+                    }
                 }
             }
+            Effect::Push(cur, sw) => {
+                let snap = scene.snapshot(&cur);
 
-            vp.apply_view(
-                ctx.bot(),
-                ctx.chat(),
-                d,
-                &view,
-                pol,
-                Some(MetaSpec {
-                    scene_id: S::ID,
-                    scene_version: S::VERSION,
-                    state_json: snap.0,
-                    state_ref: snap.1,
-                    ttl_secs: SNAP_TTL_SECS,
-                }),
-            )
-            .await?;
+                let switched = routes
+                    .switch_to_scene_with(sw.to_scene_id, sw.params.as_ref(), ctx, vp, d)
+                    .await?;
 
-            run_ui_effects(ctx.bot(), ctx.chat(), d, &ui).await;
-        }
-        Effect::SwitchScene(sw) => {
-            match routes
-                .switch_to_scene_by_id(sw.to_scene_id, ctx, vp, d)
-                .await
-            {
-                Ok(switched) => {
-                    if !switched {
+                match switched {
+                    compose::Switched::Entered => {}
+                    // Guard already answered; nothing to come back to
+                    compose::Switched::Denied => return Ok(()),
+                    compose::Switched::Unknown => {
                         tracing::warn!(
                             chat=%ctx.chat().0,
                             scene_id=%S::ID,
                             to=%sw.to_scene_id,
-                            "switch_to_scene_by_id returned false"
+                            "switch_to_scene_with: unknown scene (Push)"
+                        );
+
+                        return Ok(());
+                    }
+                }
+
+                if let Ok(mut s) = d.get_or_default().await {
+                    s.ui_push_nav_entry(NavEntry {
+                        scene_id: S::ID.to_string(),
+                        scene_version: S::VERSION,
+                        state_json: snap.0,
+                    });
+
+                    if let Err(e) = d.update(s).await {
+                        tracing::error!(
+                            error=?e,
+                            chat=%ctx.chat().0,
+                            scene_id=%S::ID,
+                            "dialogue update failed (apply_effect:Push)"
                         );
                     }
                 }
-                Err(e) => {
-                    tracing::error!(
-                        error=?e,
+            }
+            Effect::Pop => {
+                if !routes.pop_scene(ctx, vp, d).await? {
+                    tracing::debug!(
                         chat=%ctx.chat().0,
-                        to=%sw.to_scene_id,
-                        "switch_to_scene_by_id failed",
+                        scene_id=%S::ID,
+                        "navigation stack empty (Pop)"
                     );
-
-                    return Err(e);
                 }
             }
+            Effect::Noop => {}
+            Effect::NoopWithEffect(ui) => {
+                run_ui_effects(ctx.bot(), vp, ctx.chat(), d, &ui).await;
+                schedule_events::<S, _, _, _>(vp, ctx.chat(), ctx.user_id(), d, &ui).await;
+            }
         }
-        Effect::Noop => {}
-        Effect::NoopWithEffect(ui) => {
-            run_ui_effects(ctx.bot(), ctx.chat(), d, &ui).await;
-        }
+
+        return Ok(());
     }
-    Ok(())
 }
 
-#[instrument(
-    name = "router.init_and_render",
-    skip(scene, ctx, vp, d),
-    fields(
-        scene_id = %S::ID,
-        chat_id = %ctx.chat().0,
-        user_id = %ctx.user_id()
-    )
-)]
-pub async fn init_and_render<S, C, D, St, M>(
+// Render `ns` as the active scene and save its meta.
+#[allow(clippy::too_many_arguments)]
+async fn render_stay<S, C, D, St, M>(
     scene: &S,
     ctx: &C,
     vp: &Viewport<M>,
     d: &Dialogue<D, St>,
+    sctx: &SceneCtx,
+    ns: S::State,
+    pol: RenderPolicy,
+    label: &'static str,
 ) -> anyhow::Result<()>
 where
     S: Scene,
@@ -371,12 +507,8 @@ where
     <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
     M: store::Store + Send + Sync,
 {
-    let sctx = SceneCtx {
-        user_id: ctx.user_id(),
-    };
-    let state = scene.init(&sctx);
-    let view = scene.render(&sctx, &state);
-    let snap = scene.snapshot(&state);
+    let view = scene.render(sctx, &ns);
+    let snap = scene.snapshot(&ns);
 
     if let Ok(mut s) = d.get_or_default().await {
         s.ui_set_active_scene_id(Some(S::ID.to_string()));
@@ -386,7 +518,8 @@ where
                 error=?e,
                 chat=%ctx.chat().0,
                 scene_id=%S::ID,
-                "dialogue update failed (init_and_render)"
+                effect=%label,
+                "dialogue update failed (render)"
             );
         }
     }
@@ -396,13 +529,14 @@ where
         ctx.chat(),
         d,
         &view,
-        RenderPolicy::EditOrReply,
+        pol,
         Some(MetaSpec {
             scene_id: S::ID,
             scene_version: S::VERSION,
             state_json: snap.0,
             state_ref: snap.1,
             ttl_secs: SNAP_TTL_SECS,
+            owner_id: Some(ctx.user_id()),
         }),
     )
     .await?;
@@ -410,19 +544,193 @@ where
     Ok(())
 }
 
+/// Render the menu at `source` again from its meta.
+pub async fn rerender<S, C, D, St, M>(
+    scene: &S,
+    source: (ChatId, MessageId),
+    ctx: &C,
+    vp: &Viewport<M>,
+    d: &Dialogue<D, St>,
+) -> anyhow::Result<()>
+where
+    S: Scene,
+    C: AppCtx + Send + Sync,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+{
+    let sctx = scene_ctx(ctx, None, None);
+    let (state, _) = restore_state(scene, vp, d, &sctx, Some(source)).await;
+
+    render_stay(
+        scene,
+        ctx,
+        vp,
+        d,
+        &sctx,
+        state,
+        RenderPolicy::EditOrReply,
+        "rerender",
+    )
+    .await
+}
+
+#[instrument(
+    name = "router.init_and_render",
+    skip(scene, params, ctx, vp, d),
+    fields(
+        scene_id = %S::ID,
+        chat_id = %ctx.chat().0,
+        user_id = %ctx.user_id()
+    )
+)]
+pub async fn init_and_render<S, C, D, St, M>(
+    scene: &S,
+    params: Option<&serde_json::Value>,
+    ctx: &C,
+    vp: &Viewport<M>,
+    d: &Dialogue<D, St>,
+) -> anyhow::Result<()>
+where
+    S: Scene,
+    C: AppCtx + Sync,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+{
+    let sctx = scene_ctx(ctx, None, None);
+    let state = match params {
+        Some(p) => scene.init_with(&sctx, p),
+        None => scene.init(&sctx),
+    };
+
+    enter_and_render(scene, ctx, vp, d, &sctx, state, "init").await
+}
+
+#[instrument(
+    name = "router.resume_and_render",
+    skip(scene, entry, ctx, vp, d),
+    fields(
+        scene_id = %S::ID,
+        chat_id = %ctx.chat().0,
+        user_id = %ctx.user_id()
+    )
+)]
+pub async fn resume_and_render<S, C, D, St, M>(
+    scene: &S,
+    entry: &NavEntry,
+    ctx: &C,
+    vp: &Viewport<M>,
+    d: &Dialogue<D, St>,
+) -> anyhow::Result<()>
+where
+    S: Scene,
+    C: AppCtx + Sync,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+{
+    let sctx = scene_ctx(ctx, None, None);
+    let state = scene
+        .restore(crate::scene::Snapshot {
+            scene_id: &entry.scene_id,
+            scene_version: entry.scene_version,
+            state_json: entry.state_json.as_deref(),
+            state_checksum: None,
+        })
+        .unwrap_or_else(|| scene.init(&sctx));
+
+    enter_and_render(scene, ctx, vp, d, &sctx, state, "resume").await
+}
+
+async fn enter_and_render<S, C, D, St, M>(
+    scene: &S,
+    ctx: &C,
+    vp: &Viewport<M>,
+    d: &Dialogue<D, St>,
+    sctx: &SceneCtx,
+    state: S::State,
+    label: &'static str,
+) -> anyhow::Result<()>
+where
+    S: Scene,
+    C: AppCtx + Sync,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+{
+    let ui = scene.on_enter(sctx, &state);
+
+    render_stay(
+        scene,
+        ctx,
+        vp,
+        d,
+        sctx,
+        state,
+        RenderPolicy::EditOrReply,
+        label,
+    )
+    .await?;
+
+    run_ui_effects(ctx.bot(), vp, ctx.chat(), d, &ui).await;
+    schedule_events::<S, _, _, _>(vp, ctx.chat(), ctx.user_id(), d, &ui).await;
+
+    Ok(())
+}
+
+#[instrument(
+    name = "router.leave_scene",
+    skip(scene, ctx, vp, d),
+    fields(
+        scene_id = %S::ID,
+        chat_id = %ctx.chat().0,
+        user_id = %ctx.user_id()
+    )
+)]
+pub async fn leave_scene<S, C, D, St, M>(scene: &S, ctx: &C, vp: &Viewport<M>, d: &Dialogue<D, St>)
+where
+    S: Scene,
+    C: AppCtx + Sync,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+{
+    let sctx = scene_ctx(ctx, None, None);
+    let source = d
+        .get()
+        .await
+        .ok()
+        .flatten()
+        .and_then(|s| s.ui_get_last_action_message_id())
+        .map(|mid| (ctx.chat(), MessageId(mid)));
+    let (state, _) = restore_state(scene, vp, d, &sctx, source).await;
+    let ui = scene.on_leave(&sctx, &state);
+
+    run_ui_effects(ctx.bot(), vp, ctx.chat(), d, &ui).await;
+    schedule_events::<S, _, _, _>(vp, ctx.chat(), ctx.user_id(), d, &ui).await;
+}
+
 #[instrument(
     name = "router.run_cb",
-    skip(scene, routes, entry, ctx, vp, d, q),
+    skip(scene, routes, entry, decode, ctx, vp, d, q),
     fields(
         scene_id = %S::ID,
         chat_id = %ctx.chat().0,
         user_id = %ctx.user_id()
     )
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn run_cb<S, C, D, St, M, R>(
     scene: &S,
     routes: &R,
     entry: Option<&CbEntryDyn<S, C, D, St>>,
+    decode: Option<EventDecoder<S>>,
     ctx: &C,
     vp: &Viewport<M>,
     d: &Dialogue<D, St>,
@@ -437,31 +745,43 @@ where
     M: store::Store + Send + Sync,
     R: compose::RouterDispatch<C, D, St, M>,
 {
-    let sctx = SceneCtx {
-        user_id: ctx.user_id(),
-    };
+    let sctx = scene_ctx(ctx, Some(&q.from), q.message.as_ref().map(|m| m.chat()));
+
+    // Inline mode messages have no chat to
+    // render into; their state lives in inline meta.
+    if q.message.is_none()
+        && let Some(inline_id) = q.inline_message_id.as_deref()
+    {
+        let Some(ev) = cb_event(scene, decode, q) else {
+            return Ok(false);
+        };
+
+        let state = restore_inline_state(scene, vp, inline_id, &sctx).await;
+        let eff = scene.update_async(&sctx, state, ev).await;
+
+        if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
+            tracing::warn!(error=?e, "answer_callback_query failed (inline)");
+        }
+
+        apply_inline_effect(scene, ctx, vp, inline_id, &sctx, eff).await?;
+
+        return Ok(true);
+    }
 
     if let Some(handle) = entry {
         let source = q.message.as_ref().map(|m| (m.chat().id, m.id()));
         let (state, _rpath) = restore_state(scene, vp, d, &sctx, source).await;
 
         if let Some(ns) = handle(ctx.bot(), d, q, &state).await {
-            let view = scene.render(&sctx, &ns);
-            let snap = scene.snapshot(&ns);
-
-            vp.apply_view(
-                ctx.bot(),
-                ctx.chat(),
+            render_stay(
+                scene,
+                ctx,
+                vp,
                 d,
-                &view,
+                &sctx,
+                ns,
                 RenderPolicy::EditOrReply,
-                Some(MetaSpec {
-                    scene_id: S::ID,
-                    scene_version: S::VERSION,
-                    state_json: snap.0,
-                    state_ref: snap.1,
-                    ttl_secs: SNAP_TTL_SECS,
-                }),
+                "cb_entry",
             )
             .await?;
 
@@ -473,36 +793,276 @@ where
         }
     }
 
-    if let Some(ev) = scene.bindings().cb.iter().find_map(|b| (b.to_event)(q)) {
+    if let Some(ev) = cb_event(scene, decode, q) {
         let source = q.message.as_ref().map(|m| (m.chat().id, m.id()));
         let (state, _rpath) = restore_state(scene, vp, d, &sctx, source).await;
-        let eff = scene.update(&sctx, &state, ev);
-
-        apply_effect(routes, scene, ctx, vp, d, &sctx, eff).await?;
+        let eff = scene.update_async(&sctx, state, ev).await;
 
+        // Answer first: `StayWithCmd` may run a long job
+        // and the query expires after a few seconds.
         if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
             tracing::warn!(error=?e, chat=%ctx.chat().0, "answer_callback_query failed");
         }
 
+        apply_effect(routes, scene, ctx, vp, d, &sctx, eff).await?;
+
         return Ok(true);
     }
 
     Ok(false)
 }
 
+fn cb_event<S: Scene>(
+    scene: &S,
+    decode: Option<EventDecoder<S>>,
+    q: &CallbackQuery,
+) -> Option<S::Event> {
+    // Explicit bindings win over the codec
+    scene
+        .bindings()
+        .cb
+        .iter()
+        .find_map(|b| b.event(q))
+        .or_else(|| {
+            let data = q.data.as_deref()?;
+            decode?(S::PREFIX, data)
+        })
+}
+
+#[instrument(
+    name = "router.restore_inline_state",
+    skip(scene, vp, sctx),
+    fields(scene_id = %S::ID, restore_path = tracing::field::Empty)
+)]
+pub async fn restore_inline_state<S, M>(
+    scene: &S,
+    vp: &Viewport<M>,
+    inline_id: &str,
+    sctx: &SceneCtx,
+) -> S::State
+where
+    S: Scene,
+    M: store::Store + Send + Sync,
+{
+    let mut label = "init";
+    let mut state = None;
+
+    if let Ok(Some(meta)) = vp.load_inline_meta(inline_id).await {
+        state = scene.restore(crate::scene::Snapshot {
+            scene_id: &meta.scene_id,
+            scene_version: meta.scene_version,
+            state_json: meta.state_json.as_deref(),
+            state_checksum: meta.state_checksum.as_deref(),
+        });
+        label = if state.is_some() {
+            "inline"
+        } else {
+            "mismatch"
+        };
+    }
+
+    tracing::Span::current().record("restore_path", label);
+
+    #[cfg(feature = "metrics")]
+    {
+        crate::metrics::restore_state(S::ID, label);
+    }
+
+    state.unwrap_or_else(|| scene.init(sctx))
+}
+
+/// Inline mode counterpart of [`apply_effect`]; UI effects
+/// and scene navigation need a chat and are skipped.
+async fn apply_inline_effect<S, C, M>(
+    scene: &S,
+    ctx: &C,
+    vp: &Viewport<M>,
+    inline_id: &str,
+    sctx: &SceneCtx,
+    eff: Effect<S::State, S::Event>,
+) -> anyhow::Result<()>
+where
+    S: Scene,
+    C: AppCtx + Send + Sync,
+    M: store::Store + Send + Sync,
+{
+    let mut eff = eff;
+
+    loop {
+        let (ns, cmd) = match eff {
+            Effect::Stay(ns, _) | Effect::StayWithEffect(ns, _, _) => (ns, None),
+            Effect::StayWithCmd(ns, _, cmd) => (ns, Some(cmd)),
+            Effect::Noop | Effect::NoopWithEffect(_) => return Ok(()),
+            Effect::SwitchScene(_) | Effect::Push(_, _) | Effect::Pop => {
+                tracing::debug!(scene_id=%S::ID, "scene navigation skipped on inline message");
+                return Ok(());
+            }
+        };
+
+        let view = scene.render(sctx, &ns);
+        let snap = scene.snapshot(&ns);
+
+        vp.apply_inline_view(
+            ctx.bot(),
+            inline_id,
+            &view,
+            Some(MetaSpec {
+                scene_id: S::ID,
+                scene_version: S::VERSION,
+                state_json: snap.0,
+                state_ref: snap.1,
+                ttl_secs: SNAP_TTL_SECS,
+                owner_id: None,
+            }),
+        )
+        .await?;
+
+        let Some(cmd) = cmd else {
+            return Ok(());
+        };
+        let ev = cmd.run().await;
+
+        eff = scene.update_async(sctx, ns, ev).await;
+    }
+}
+
+#[instrument(
+    name = "router.run_inline_query",
+    skip(scene, ctx, vp, q),
+    fields(scene_id = %S::ID, user_id = %q.from.id.0)
+)]
+pub async fn run_inline_query<S, C, M>(
+    scene: &S,
+    ctx: &C,
+    vp: &Viewport<M>,
+    q: &InlineQuery,
+) -> anyhow::Result<bool>
+where
+    S: Scene,
+    C: AppCtx + Send + Sync,
+    M: store::Store + Send + Sync,
+{
+    let sctx = scene_ctx(ctx, Some(&q.from), None);
+    let Some(results) = scene.inline_query(&sctx, &q.query) else {
+        return Ok(false);
+    };
+
+    let mut answers = Vec::with_capacity(results.len());
+    for r in results {
+        let view = scene.render(&sctx, &r.state);
+        let snap = scene.snapshot(&r.state);
+        let spec = MetaSpec {
+            scene_id: S::ID,
+            scene_version: S::VERSION,
+            state_json: snap.0,
+            state_ref: snap.1,
+            ttl_secs: SNAP_TTL_SECS,
+            owner_id: None,
+        };
+
+        answers.push(
+            vp.inline_article(&view, r.title, r.description, spec)
+                .await?,
+        );
+    }
+
+    // Results carry per-user state
+    if let Err(e) = ctx
+        .bot()
+        .answer_inline_query(q.id.clone(), answers)
+        .is_personal(true)
+        .await
+    {
+        tracing::warn!(error=?e, "answer_inline_query failed");
+    }
+
+    Ok(true)
+}
+
+fn match_msg<'m>(
+    pattern: &MsgPattern,
+    m: &'m Message,
+    regexes: &MsgRegexes,
+    bot_username: Option<&str>,
+    prompt_active: bool,
+) -> Option<MsgMatch<'m>> {
+    let text = m.text();
+
+    match *pattern {
+        // Media with a caption is left to the media patterns
+        MsgPattern::AnyText => text
+            .filter(|_| prompt_active)
+            .map(|t| MsgMatch::new(t, None)),
+        MsgPattern::Photo => media_match(m, prompt_active && m.photo().is_some()),
+        MsgPattern::Document => media_match(m, prompt_active && m.document().is_some()),
+        MsgPattern::Voice => media_match(m, prompt_active && m.voice().is_some()),
+        MsgPattern::Location => media_match(m, prompt_active && m.location().is_some()),
+        MsgPattern::Contact => media_match(m, prompt_active && m.contact().is_some()),
+        MsgPattern::Sticker => media_match(m, prompt_active && m.sticker().is_some()),
+        MsgPattern::Button(label) => (text? == label).then(|| MsgMatch::new("", None)),
+        MsgPattern::Command(name) => {
+            let args = match_command(text?, name, bot_username)?;
+            Some(MsgMatch::new(args, None))
+        }
+        MsgPattern::Regex(src) => {
+            let Some(re) = regexes.get(src) else {
+                tracing::warn!(pattern = src, "regex pattern not compiled");
+                return None;
+            };
+            let text = text?;
+            let caps = re.captures(text)?;
+            Some(MsgMatch::new(text, Some(caps)))
+        }
+    }
+}
+
+fn media_match(m: &Message, matched: bool) -> Option<MsgMatch<'_>> {
+    matched.then(|| MsgMatch::new(m.caption().unwrap_or(""), None))
+}
+
+/// Arguments of `/name args` or `/name@bot args`;
+/// any `@bot` is accepted when the username is unknown.
+pub(crate) fn match_command<'t>(
+    text: &'t str,
+    name: &str,
+    bot_username: Option<&str>,
+) -> Option<&'t str> {
+    let rest = text.strip_prefix('/')?;
+    let (head, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let (cmd, mention) = match head.split_once('@') {
+        Some((cmd, bot)) => (cmd, Some(bot)),
+        None => (head, None),
+    };
+
+    if cmd != name.trim_start_matches('/') {
+        return None;
+    }
+    if let (Some(bot), Some(me)) = (mention, bot_username)
+        && !bot.eq_ignore_ascii_case(me.trim_start_matches('@'))
+    {
+        return None;
+    }
+
+    Some(args.trim())
+}
+
 #[instrument(
     name = "router.run_msg",
-    skip(scene, routes, entry, ctx, vp, d, m),
+    skip(scene, routes, entry, regexes, guard, ctx, vp, d, m),
     fields(
         scene_id = %S::ID,
         chat_id = %ctx.chat().0,
         user_id = %ctx.user_id()
     )
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn run_msg<S, C, D, St, M, R>(
     scene: &S,
     routes: &R,
     entry: Option<&MsgEntryDyn<S, C, D, St>>,
+    regexes: &MsgRegexes,
+    // Checked once a message matches, not before
+    guard: Option<&Guard<C>>,
     ctx: &C,
     vp: &Viewport<M>,
     d: &Dialogue<D, St>,
@@ -517,53 +1077,46 @@ where
     M: store::Store + Send + Sync,
     R: compose::RouterDispatch<C, D, St, M>,
 {
-    let sctx = SceneCtx {
-        user_id: ctx.user_id(),
-    };
+    let sctx = scene_ctx(ctx, m.from.as_ref(), Some(&m.chat));
 
     // if prompt active, try entry flow via viewport meta
-    if let Ok(Some(s)) = d.get().await {
-        if let Some(pid) = s.ui_get_input_prompt_message_id() {
-            if let Ok(Some(meta)) = vp.load_meta(ctx.chat(), pid).await {
-                if let Some(cur) = scene.restore(crate::scene::Snapshot {
-                    scene_id: &meta.scene_id,
-                    scene_version: meta.scene_version,
-                    state_json: meta.state_json.as_deref(),
-                    state_checksum: meta.state_checksum.as_deref(),
-                }) {
-                    let _deleted = message::delete_incoming(ctx.bot(), m).await;
-
-                    if let Some(handle) = entry {
-                        if let Some(ns) = handle(ctx.bot(), d, m, &cur).await {
-                            let view = scene.render(&sctx, &ns);
-                            let snap = scene.snapshot(&ns);
-
-                            vp.apply_view(
-                                ctx.bot(),
-                                ctx.chat(),
-                                d,
-                                &view,
-                                RenderPolicy::EditOrReply,
-                                Some(MetaSpec {
-                                    scene_id: S::ID,
-                                    scene_version: S::VERSION,
-                                    state_json: snap.0,
-                                    state_ref: snap.1,
-                                    ttl_secs: SNAP_TTL_SECS,
-                                }),
-                            )
-                            .await?;
-
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
+    if let Ok(Some(s)) = d.get().await
+        && let Some(pid) = s.ui_get_input_prompt_message_id()
+        && let Ok(Some(meta)) = vp.load_meta(ctx.chat(), pid).await
+        && let Some(cur) = scene.restore(crate::scene::Snapshot {
+            scene_id: &meta.scene_id,
+            scene_version: meta.scene_version,
+            state_json: meta.state_json.as_deref(),
+            state_checksum: meta.state_checksum.as_deref(),
+        })
+    {
+        if !allowed(guard, ctx).await {
+            return Ok(false);
+        }
+
+        let _deleted = message::delete_incoming(ctx.bot(), m).await;
+
+        if let Some(handle) = entry
+            && let Some(ns) = handle(ctx.bot(), d, m, &cur).await
+        {
+            render_stay(
+                scene,
+                ctx,
+                vp,
+                d,
+                &sctx,
+                ns,
+                RenderPolicy::EditOrReply,
+                "msg_entry",
+            )
+            .await?;
+
+            return Ok(true);
         }
     }
 
     // otherwise try message bindings;
-    // gate AnyText by prompt presence.
+    // gate AnyText and media by prompt presence.
     let prompt_active = d
         .get()
         .await
@@ -572,12 +1125,17 @@ where
         .and_then(|s| s.ui_get_input_prompt_message_id())
         .is_some();
 
-    if let Some(ev) = scene.bindings().msg.iter().find_map(|b| match b.pattern {
-        MsgPattern::AnyText if !prompt_active => None,
-        _ => (b.to_event)(m),
+    let bot_username = ctx.bot_username();
+    if let Some(ev) = scene.bindings().msg.iter().find_map(|b| {
+        let found = match_msg(&b.pattern, m, regexes, bot_username, prompt_active)?;
+        (b.to_event)(m, &found)
     }) {
+        if !allowed(guard, ctx).await {
+            return Ok(false);
+        }
+
         let (state, _rpath) = restore_state(scene, vp, d, &sctx, None).await;
-        let eff = scene.update(&sctx, &state, ev);
+        let eff = scene.update_async(&sctx, state, ev).await;
 
         apply_effect(routes, scene, ctx, vp, d, &sctx, eff).await?;
 
@@ -586,3 +1144,95 @@ where
 
     Ok(false)
 }
+
+#[instrument(
+    name = "router.run_scheduled",
+    skip(scene, routes, decode, ctx, vp, d, ev),
+    fields(
+        scene_id = %S::ID,
+        chat_id = %ev.chat_id,
+        mid = %ev.message_id
+    )
+)]
+pub async fn run_scheduled<S, C, D, St, M, R>(
+    scene: &S,
+    routes: &R,
+    decode: Option<EventDecoder<S>>,
+    ctx: &C,
+    vp: &Viewport<M>,
+    d: &Dialogue<D, St>,
+    ev: &ScheduledEvent,
+) -> anyhow::Result<bool>
+where
+    S: Scene,
+    C: AppCtx + Send + Sync,
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    M: store::Store + Send + Sync,
+    R: compose::RouterDispatch<C, D, St, M>,
+{
+    let Some(decode) = decode else {
+        tracing::warn!(scene_id=%S::ID, "scheduled event for scene without codec");
+        return Ok(false);
+    };
+    let Some(event) = decode(S::PREFIX, &ev.payload) else {
+        tracing::warn!(scene_id=%S::ID, "scheduled event payload decode failed");
+        return Ok(false);
+    };
+
+    let sctx = scene_ctx(ctx, None, None);
+    let source = Some((ChatId(ev.chat_id), MessageId(ev.message_id)));
+    let (state, rpath) = restore_state(scene, vp, d, &sctx, source).await;
+
+    // Target message is gone or belongs
+    // to another scene; nothing to update.
+    if rpath == "init" || rpath == "mismatch" {
+        tracing::debug!(scene_id=%S::ID, restore_path=%rpath, "scheduled event target expired");
+        return Ok(false);
+    }
+
+    // Render into the message the event was scheduled for, then
+    // hand the session back to wherever the user has moved on to.
+    let prev = match d.get_or_default().await {
+        Ok(s) => (
+            s.ui_get_last_action_message_id(),
+            s.ui_get_active_scene_id(),
+        ),
+        Err(_) => (None, None),
+    };
+    let moved = prev != (Some(ev.message_id), Some(S::ID.to_string()));
+    set_render_target(d, Some(ev.message_id), Some(S::ID.to_string())).await;
+
+    // Only ever edit the timer's own message
+    let eff = match scene.update_async(&sctx, state, event).await {
+        Effect::Stay(s, _) => Effect::Stay(s, RenderPolicy::EditOnly),
+        Effect::StayWithEffect(s, _, fx) => Effect::StayWithEffect(s, RenderPolicy::EditOnly, fx),
+        Effect::StayWithCmd(s, _, cmd) => Effect::StayWithCmd(s, RenderPolicy::EditOnly, cmd),
+        eff => eff,
+    };
+
+    let res = apply_effect(routes, scene, ctx, vp, d, &sctx, eff).await;
+
+    if moved {
+        set_render_target(d, prev.0, prev.1).await;
+    }
+
+    res.map(|()| true)
+}
+
+async fn set_render_target<D, St>(d: &Dialogue<D, St>, mid: Option<i32>, scene_id: Option<String>)
+where
+    D: UiStore + Send + Sync,
+    St: UiDialogueStorage<D>,
+    <St as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+{
+    if let Ok(mut s) = d.get_or_default().await {
+        s.ui_set_last_action_message_id(mid);
+        s.ui_set_active_scene_id(scene_id);
+
+        if let Err(e) = d.update(s).await {
+            tracing::error!(error=?e, mid=?mid, "dialogue update failed (run_scheduled)");
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as B64};
+
+/// Telegram's limit for the `start` parameter.
+pub const START_PARAM_MAX: usize = 64;
+
+/// Turns a deep-link payload (after the scene prefix)
+/// into params for `Scene::init_with`.
+pub type DeepLinkDecoder = fn(&str) -> Option<serde_json::Value>;
+
+/// `{prefix}-{base64url(json)}`, or `None` if the prefix isn't
+/// `[A-Za-z0-9_]` or the result exceeds [`START_PARAM_MAX`].
+pub fn start_param<P: serde::Serialize>(prefix: &str, params: &P) -> Option<String> {
+    if prefix.is_empty()
+        || !prefix
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    {
+        return None;
+    }
+
+    let json = serde_json::to_vec(params).ok()?;
+    let param = format!("{prefix}-{}", B64.encode(json));
+
+    (param.len() <= START_PARAM_MAX).then_some(param)
+}
+
+/// `https://t.me/{bot}?start={param}` opening the scene with `prefix`.
+pub fn start_link<P: serde::Serialize>(
+    bot_username: &str,
+    prefix: &str,
+    params: &P,
+) -> Option<String> {
+    let param = start_param(prefix, params)?;
+    Some(format!(
+        "https://t.me/{}?start={param}",
+        bot_username.trim_start_matches('@')
+    ))
+}
+
+/// Scene prefix and payload of a `start` parameter.
+pub fn split_start_param(param: &str) -> Option<(&str, &str)> {
+    param.split_once('-')
+}
+
+/// Default [`DeepLinkDecoder`], the inverse of [`start_param`].
+pub fn decode_json(payload: &str) -> Option<serde_json::Value> {
+    let bytes = B64.decode(payload).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
@@ -0,0 +1,28 @@
+use crate::router::AppEvent;
+
+use std::future::Future;
+use std::pin::Pin;
+use teloxide::dispatching::dialogue::Dialogue;
+
+/// The rest of the chain: later layers, then scene dispatch.
+/// Dropping it without [`Next::run`] short-circuits the update.
+pub struct Next<'a>(pub(crate) Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>);
+
+impl Next<'_> {
+    pub async fn run(self) -> anyhow::Result<()> {
+        self.0.await
+    }
+}
+
+/// Runs around every `Router::handle`: may edit the session, skip
+/// [`Next::run`] or look at its result. See `RouterBuilder::layer`.
+#[async_trait::async_trait]
+pub trait Layer<C, D, S>: Send + Sync {
+    async fn handle<'a>(
+        &'a self,
+        ctx: &'a C,
+        d: &'a Dialogue<D, S>,
+        ev: &'a AppEvent<'a>,
+        next: Next<'a>,
+    ) -> anyhow::Result<()>;
+}
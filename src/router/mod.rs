@@ -1,33 +1,96 @@
 pub mod compose;
 pub mod core;
+pub mod deep_link;
+pub mod layer;
 
+use crate::router::compose::{Admission, RouteTypes, Switched};
+use crate::router::core::match_command;
+use crate::router::layer::{Layer, Next};
+use crate::scene::{CALLBACK_DATA_MAX, ChatKind};
 use crate::session::{UiDialogueStorage, UiStore};
-use crate::ui::callback;
+use crate::ui::callback::{self, CallbackSigner};
 use crate::ui::message::{clear_input_prompt_message, delete_incoming};
 use crate::ui::prelude::UiRequester;
-use crate::viewport::Viewport;
-use crate::viewport::store::Store;
+use crate::viewport::store::{RateLimit, Store};
+use crate::viewport::{MessageMeta, ScheduledEvent, Viewport};
 
 use dialogue::Dialogue;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 use teloxide::{
     dispatching::dialogue,
     payloads::AnswerCallbackQuerySetters,
     prelude::Requester,
-    types::{CallbackQuery, ChatId, Message},
+    types::{
+        CallbackQuery, ChatId, ChatMemberUpdated, ChosenInlineResult, InlineQuery, Message,
+        MessageId,
+    },
 };
 
+#[derive(Clone, Copy)]
 pub enum AppEvent<'a> {
     Msg(&'a Message),
     Cb(&'a CallbackQuery),
+    InlineQuery(&'a InlineQuery),
+    /// Needs inline feedback enabled in
+    /// @BotFather for results to stay interactive.
+    ChosenInlineResult(&'a ChosenInlineResult),
+    /// The bot was blocked, unblocked, added or removed.
+    MyChatMember(&'a ChatMemberUpdated),
+    ChatMember(&'a ChatMemberUpdated),
 }
 
+/// Membership change passed to [`RouterBuilder::on_member_update`].
+#[derive(Clone, Debug)]
+pub enum MemberEvent {
+    /// The bot's own membership; `reachable` is
+    /// false once it was blocked or removed.
+    Bot {
+        update: ChatMemberUpdated,
+        reachable: bool,
+    },
+    User(ChatMemberUpdated),
+}
+
+type MemberHook =
+    Arc<dyn Fn(MemberEvent) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// What to do with a callback or message no route
+/// handled, see [`RouterBuilder::on_unhandled`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Fallback {
+    /// "No longer active" alert for callbacks; messages are
+    /// deleted when `with_clear_noise` is on.
+    #[default]
+    Default,
+    /// Render the menu again from its meta.
+    Rerender,
+    SwitchTo(&'static str),
+    /// Callback alert, or a plain reply to a message.
+    Alert(String),
+    Ignore,
+}
+
+type Layers<R> = Vec<
+    Arc<dyn Layer<<R as RouteTypes>::Ctx, <R as RouteTypes>::Session, <R as RouteTypes>::Storage>>,
+>;
+
+type FallbackHook = Arc<dyn Fn(&AppEvent<'_>, Option<&MessageMeta>) -> Fallback + Send + Sync>;
+
 pub trait AppCtx
 where
     <Self::Bot as Requester>::SendMessage: Send,
     <Self::Bot as Requester>::EditMessageText: Send,
     <Self::Bot as Requester>::DeleteMessage: Send,
     <Self::Bot as Requester>::AnswerCallbackQuery: Send,
+    <Self::Bot as Requester>::SendPhoto: Send,
+    <Self::Bot as Requester>::SendAnimation: Send,
+    <Self::Bot as Requester>::SendDocument: Send,
+    <Self::Bot as Requester>::EditMessageMedia: Send,
+    <Self::Bot as Requester>::EditMessageCaption: Send,
+    <Self::Bot as Requester>::EditMessageTextInline: Send,
+    <Self::Bot as Requester>::AnswerInlineQuery: Send,
 {
     type Bot: UiRequester;
 
@@ -37,12 +100,31 @@ where
     fn username(&self) -> Option<&str> {
         None
     }
+    fn language_code(&self) -> Option<&str> {
+        None
+    }
+    fn chat_kind(&self) -> ChatKind {
+        ChatKind::Private
+    }
+    /// Username of the bot; `/cmd@other_bot`
+    /// is ignored once this is known.
+    fn bot_username(&self) -> Option<&str> {
+        None
+    }
 }
 
-#[derive(Default)]
-pub struct RouterBuilder<R> {
+pub struct RouterBuilder<R: RouteTypes> {
     routes: Option<Arc<R>>,
     clear_noise: bool,
+    signer: Option<CallbackSigner>,
+    member_hook: Option<MemberHook>,
+    group_mode: bool,
+    home: Option<&'static str>,
+    fallback: Option<FallbackHook>,
+    layers: Layers<R>,
+    user_limit: Option<RateLimit>,
+    chat_limit: Option<RateLimit>,
+    throttle_text: String,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -51,11 +133,26 @@ pub enum BuildError {
     RoutesNotConfigured,
 }
 
-impl<R> RouterBuilder<R> {
+impl<R: RouteTypes> Default for RouterBuilder<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: RouteTypes> RouterBuilder<R> {
     pub fn new() -> Self {
         Self {
             routes: None,
             clear_noise: true,
+            signer: None,
+            member_hook: None,
+            group_mode: false,
+            home: None,
+            fallback: None,
+            layers: Vec::new(),
+            user_limit: None,
+            chat_limit: None,
+            throttle_text: "Too many requests, slow down.".into(),
         }
     }
 
@@ -69,29 +166,124 @@ impl<R> RouterBuilder<R> {
         self
     }
 
+    /// Reject callbacks without a valid signature; pair
+    /// with `Viewport::with_callback_secret` and the same secret.
+    pub fn with_callback_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.signer = Some(CallbackSigner::new(secret));
+        self
+    }
+
+    /// Only the user a menu was rendered for may press its buttons
+    /// in groups; key dialogues with `session::dialogue_key`.
+    pub fn with_group_mode(mut self, on: bool) -> Self {
+        self.group_mode = on;
+        self
+    }
+
+    /// Scene entered on `/start` without a deep link
+    /// (or one no scene accepts).
+    pub fn with_home_scene(mut self, scene_id: &'static str) -> Self {
+        self.home = Some(scene_id);
+        self
+    }
+
+    /// Decide what happens to unmatched callbacks and messages. The meta
+    /// is the pressed menu's, or the last menu's for messages.
+    pub fn on_unhandled<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&AppEvent<'_>, Option<&MessageMeta>) -> Fallback + Send + Sync + 'static,
+    {
+        self.fallback = Some(Arc::new(f));
+        self
+    }
+
+    /// Token bucket per user; messages over it are
+    /// dropped, callbacks get the throttle toast.
+    pub fn with_user_limit(mut self, limit: RateLimit) -> Self {
+        self.user_limit = Some(limit);
+        self
+    }
+
+    /// Token bucket per chat, shared by all of its members.
+    pub fn with_chat_limit(mut self, limit: RateLimit) -> Self {
+        self.chat_limit = Some(limit);
+        self
+    }
+
+    /// Toast shown for throttled callbacks and `CbBinding::cooldown`.
+    pub fn with_throttle_text(mut self, text: impl Into<String>) -> Self {
+        self.throttle_text = text.into();
+        self
+    }
+
+    /// Wrap every update; the first layer added runs outermost.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<R::Ctx, R::Session, R::Storage> + 'static,
+    {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    pub fn on_member_update<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(MemberEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.member_hook = Some(Arc::new(move |ev| Box::pin(f(ev))));
+        self
+    }
+
     pub fn build(self) -> Result<Router<R>, BuildError> {
         Ok(Router {
             routes: self.routes.ok_or(BuildError::RoutesNotConfigured)?,
             clear_noise: self.clear_noise,
+            signer: self.signer,
+            member_hook: self.member_hook,
+            group_mode: self.group_mode,
+            home: self.home,
+            fallback: self.fallback,
+            layers: self.layers,
+            user_limit: self.user_limit,
+            chat_limit: self.chat_limit,
+            throttle_text: self.throttle_text,
         })
     }
 }
 
-pub struct Router<R> {
+pub struct Router<R: RouteTypes> {
     routes: Arc<R>,
     clear_noise: bool,
+    signer: Option<CallbackSigner>,
+    member_hook: Option<MemberHook>,
+    group_mode: bool,
+    home: Option<&'static str>,
+    fallback: Option<FallbackHook>,
+    layers: Layers<R>,
+    user_limit: Option<RateLimit>,
+    chat_limit: Option<RateLimit>,
+    throttle_text: String,
 }
 
-impl<R> Clone for Router<R> {
+impl<R: RouteTypes> Clone for Router<R> {
     fn clone(&self) -> Self {
         Self {
             routes: Arc::clone(&self.routes),
             clear_noise: self.clear_noise,
+            signer: self.signer.clone(),
+            member_hook: self.member_hook.clone(),
+            group_mode: self.group_mode,
+            home: self.home,
+            fallback: self.fallback.clone(),
+            layers: self.layers.clone(),
+            user_limit: self.user_limit,
+            chat_limit: self.chat_limit,
+            throttle_text: self.throttle_text.clone(),
         }
     }
 }
 
-impl<R> Router<R> {
+impl<R: RouteTypes> Router<R> {
     #[tracing::instrument(
         name = "router.handle",
         skip(self, ctx, vp, d, ev),
@@ -113,7 +305,9 @@ impl<R> Router<R> {
         D: UiStore + Send + Sync,
         S: UiDialogueStorage<D>,
         M: Store + Send + Sync,
-        R: compose::RouterDispatch<C, D, S, M> + compose::SceneLookup,
+        R: compose::RouterDispatch<C, D, S, M>
+            + compose::SceneLookup
+            + RouteTypes<Ctx = C, Session = D, Storage = S>,
         <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
     {
         if let Some(u) = ctx.username() {
@@ -125,16 +319,75 @@ impl<R> Router<R> {
             let kind: &'static str = match ev {
                 AppEvent::Msg(_) => "msg",
                 AppEvent::Cb(_) => "cb",
+                AppEvent::InlineQuery(_) => "inline_query",
+                AppEvent::ChosenInlineResult(_) => "chosen_inline_result",
+                AppEvent::MyChatMember(_) => "my_chat_member",
+                AppEvent::ChatMember(_) => "chat_member",
             };
             crate::metrics::router_handle(kind, ctx.chat().0, ctx.user_id());
         }
 
+        if self.layers.is_empty() {
+            return self.dispatch(ctx, vp, d, ev).await;
+        }
+
+        let mut next = Next(Box::pin(self.dispatch(ctx, vp, d, ev)));
+        for layer in self.layers.iter().rev() {
+            next = Next(layer.handle(ctx, d, &ev, next));
+        }
+
+        next.run().await
+    }
+
+    async fn dispatch<C, D, S, M>(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, S>,
+        ev: AppEvent<'_>,
+    ) -> anyhow::Result<()>
+    where
+        C: AppCtx + Send + Sync,
+        D: UiStore + Send + Sync,
+        S: UiDialogueStorage<D>,
+        M: Store + Send + Sync,
+        R: compose::RouterDispatch<C, D, S, M> + compose::SceneLookup,
+        <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    {
+        // Anyone writing to the bot can be rendered to again
+        if matches!(ev, AppEvent::Msg(_) | AppEvent::Cb(_)) {
+            vp.set_unreachable(ctx.chat(), false).await;
+
+            if let Some(limit) = self.over_limit(ctx, vp).await {
+                tracing::debug!(limit, "update throttled");
+
+                #[cfg(feature = "metrics")]
+                crate::metrics::throttled(limit);
+
+                if let AppEvent::Cb(q) = ev {
+                    self.answer_throttled(ctx, q).await;
+                }
+
+                return Ok(());
+            }
+        }
+
         match ev {
             AppEvent::Msg(m) => {
                 // Commands
-                if let Some(text) = m.text() {
-                    if text.trim_start().starts_with("/start") {
-                        // TODO: app chooses behaviour.
+                if let Some(text) = m.text()
+                    && let Some(param) =
+                        match_command(text.trim_start(), "start", ctx.bot_username())
+                {
+                    if !param.is_empty() && self.routes.open_deep_link(param, ctx, vp, d).await? {
+                        return Ok(());
+                    }
+
+                    if let Some(home) = self.home
+                        && self.routes.switch_to_scene_by_id(home, ctx, vp, d).await?
+                            != Switched::Unknown
+                    {
+                        return Ok(());
                     }
                 }
 
@@ -146,15 +399,14 @@ impl<R> Router<R> {
                             .routes
                             .handle_msg(Some(active.as_str()), ctx, vp, d, m)
                             .await?;
-                    } else if let Some(last) = s.ui_get_last_action_message_id() {
-                        if let Some(json) = s.ui_get_scene_for_message(last) {
-                            if let Ok(id) = serde_json::from_str::<String>(&json) {
-                                handled = self
-                                    .routes
-                                    .handle_msg(Some(id.as_str()), ctx, vp, d, m)
-                                    .await?;
-                            }
-                        }
+                    } else if let Some(last) = s.ui_get_last_action_message_id()
+                        && let Some(json) = s.ui_get_scene_for_message(last)
+                        && let Ok(id) = serde_json::from_str::<String>(&json)
+                    {
+                        handled = self
+                            .routes
+                            .handle_msg(Some(id.as_str()), ctx, vp, d, m)
+                            .await?;
                     }
                 }
 
@@ -162,40 +414,176 @@ impl<R> Router<R> {
                     return Ok(());
                 }
 
+                let source = if self.fallback.is_some()
+                    && let Ok(Some(s)) = d.get().await
+                {
+                    s.ui_get_last_action_message_id()
+                        .map(|mid| (ctx.chat(), MessageId(mid)))
+                } else {
+                    None
+                };
+                let (fallback, meta) = self.fallback(&AppEvent::Msg(m), vp, source).await;
+
+                match fallback {
+                    Fallback::Ignore => return Ok(()),
+                    Fallback::Default => {}
+                    Fallback::Rerender => {
+                        if let (Some(meta), Some(source)) = (meta, source) {
+                            self.routes
+                                .rerender(&meta.scene_id, source, ctx, vp, d)
+                                .await?;
+                        }
+                    }
+                    Fallback::SwitchTo(id) => {
+                        self.routes.switch_to_scene_by_id(id, ctx, vp, d).await?;
+                    }
+                    Fallback::Alert(text) => {
+                        if let Err(e) = ctx.bot().send_message(ctx.chat(), text).await {
+                            tracing::warn!(error=?e, "send_message failed (unhandled message)");
+                        }
+                    }
+                }
+
                 if self.clear_noise {
                     let _deleted = delete_incoming(ctx.bot(), m).await;
                 }
             }
             AppEvent::Cb(q) => {
-                vp.activate_from_callback(d, q, self.routes.as_ref()).await;
-
                 // Validate callback payload
                 // size/charset to reduce abuse.
-                if let Some(data) = q.data.as_deref() {
-                    if data.len() > 64 || !data.is_ascii() {
+                if let Some(data) = q.data.as_deref()
+                    && (data.len() > CALLBACK_DATA_MAX || !data.is_ascii())
+                {
+                    tracing::warn!(
+                        len = data.len(),
+                        ascii = data.is_ascii(),
+                        "callback data rejected"
+                    );
+
+                    #[cfg(feature = "metrics")]
+                    {
+                        let reason = if data.is_ascii() {
+                            "oversize"
+                        } else {
+                            "non_ascii"
+                        };
+                        crate::metrics::callback_rejected(reason);
+                    }
+
+                    if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
+                        tracing::warn!(error=?e, "answer_callback_query failed (invalid callback)");
+                    }
+
+                    return Ok(());
+                }
+
+                let mut data = q.data.clone();
+
+                // Strip the signature before anything reads the data
+                if let Some(signer) = &self.signer
+                    && let Some(raw) = q.data.as_deref()
+                    && CallbackSigner::needs_signature(raw)
+                {
+                    let Some(verified) = signer.verify(raw) else {
+                        tracing::warn!(len = raw.len(), "callback signature rejected");
+
+                        #[cfg(feature = "metrics")]
+                        crate::metrics::callback_rejected("bad_signature");
+
                         if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
-                            tracing::warn!(error=?e, "answer_callback_query failed (invalid callback)");
+                            tracing::warn!(error=?e, "answer_callback_query failed (bad signature)");
                         }
 
                         return Ok(());
+                    };
+
+                    data = Some(verified.to_string());
+                }
+
+                // Swap a payload token for the stored payload
+                if let Some(token) = data.as_deref()
+                    && token.starts_with(callback::PAYLOAD_TOKEN)
+                {
+                    match vp.load_payload(token).await {
+                        Ok(Some(payload)) => data = Some(payload),
+                        res => {
+                            if let Err(e) = res {
+                                tracing::warn!(error=?e, "load callback payload failed");
+                            }
+
+                            answer_stale(ctx, q).await;
+                            return Ok(());
+                        }
+                    }
+                }
+
+                let resolved;
+                let q = if data != q.data {
+                    resolved = CallbackQuery { data, ..q.clone() };
+                    &resolved
+                } else {
+                    q
+                };
+
+                if self.group_mode
+                    && let Some(msg) = &q.message
+                    && (msg.chat().is_group() || msg.chat().is_supergroup())
+                    && let Ok(Some(meta)) = vp.load_meta(msg.chat().id, msg.id().0).await
+                    && meta
+                        .owner_id
+                        .is_some_and(|owner| owner != q.from.id.0 as i64)
+                {
+                    if let Err(e) = ctx
+                        .bot()
+                        .answer_callback_query(q.id.clone())
+                        .text("This menu belongs to another user.")
+                        .show_alert(true)
+                        .await
+                    {
+                        tracing::warn!(error=?e, "answer_callback_query failed (foreign menu)");
                     }
+
+                    return Ok(());
+                }
+
+                if let Some((key, cooldown)) = self.routes.callback_cooldown(q)
+                    && !vp
+                        .take_token(
+                            &format!("cd:{}:{key}", q.from.id.0),
+                            RateLimit::cooldown(cooldown),
+                        )
+                        .await
+                {
+                    #[cfg(feature = "metrics")]
+                    crate::metrics::throttled("cooldown");
+
+                    self.answer_throttled(ctx, q).await;
+                    return Ok(());
                 }
 
+                // Guard before the scene becomes active in the session
+                let admitted = match self.routes.admit_callback(ctx, vp, d, q).await? {
+                    Admission::Admitted(id) => id,
+                    Admission::Denied => return Ok(()),
+                };
+
+                vp.activate_from_callback(d, q, self.routes.as_ref(), self.group_mode)
+                    .await;
+
                 // UI actions first
                 if let Some(data) = q.data.as_deref() {
                     if data == callback::CANCEL {
                         clear_input_prompt_message(ctx.bot(), ctx.chat(), d).await;
 
-                        if let Some(msg) = &q.message {
-                            if let Err(e) = ctx.bot().delete_message(msg.chat().id, msg.id()).await
-                            {
-                                tracing::warn!(
-                                    error=?e,
-                                    chat=%msg.chat().id.0,
-                                    mid=%msg.id().0,
-                                    "delete message failed (CANCEL)"
-                                );
-                            }
+                        if let Some(msg) = &q.message
+                            && let Err(e) = ctx.bot().delete_message(msg.chat().id, msg.id()).await
+                        {
+                            tracing::warn!(
+                                error=?e,
+                                chat=%msg.chat().id.0,
+                                mid=%msg.id().0,
+                                "delete message failed (CANCEL)"
+                            );
                         }
 
                         if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
@@ -210,16 +598,25 @@ impl<R> Router<R> {
                             tracing::warn!(error=?e, "answer_callback_query failed (HIDE)");
                         }
 
-                        if let Some(msg) = &q.message {
-                            if let Err(e) = ctx.bot().delete_message(msg.chat().id, msg.id()).await
-                            {
-                                tracing::warn!(
-                                    error=?e,
-                                    chat=%msg.chat().id.0,
-                                    mid=%msg.id().0,
-                                    "delete message failed (HIDE)"
-                                );
-                            }
+                        if let Some(msg) = &q.message
+                            && let Err(e) = ctx.bot().delete_message(msg.chat().id, msg.id()).await
+                        {
+                            tracing::warn!(
+                                error=?e,
+                                chat=%msg.chat().id.0,
+                                mid=%msg.id().0,
+                                "delete message failed (HIDE)"
+                            );
+                        }
+
+                        return Ok(());
+                    }
+
+                    // Built-in back navigation; scenes may still
+                    // bind BACK themselves when the stack is empty.
+                    if data == callback::BACK && self.routes.pop_scene(ctx, vp, d).await? {
+                        if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
+                            tracing::warn!(error=?e, "answer_callback_query failed (BACK)");
                         }
 
                         return Ok(());
@@ -235,22 +632,179 @@ impl<R> Router<R> {
                 }
 
                 // Declarative callback routing via routes
-                if self.routes.handle_cb(ctx, vp, d, q).await? {
+                if self
+                    .routes
+                    .handle_admitted_cb(admitted, ctx, vp, d, q)
+                    .await?
+                {
                     return Ok(());
                 }
 
                 // Unknown fallback
-                if let Err(e) = ctx
-                    .bot()
-                    .answer_callback_query(q.id.clone())
-                    .text("This menu is no longer active, enter /start command and open this section again.")
-                    .show_alert(true)
-                    .await {
-                    tracing::warn!(error=?e, "answer_callback_query failed (unknown fallback)");
+                let source = q.message.as_ref().map(|msg| (msg.chat().id, msg.id()));
+                let (fallback, meta) = self.fallback(&AppEvent::Cb(q), vp, source).await;
+
+                let handled = match (fallback, source) {
+                    (Fallback::Default, _) => false,
+                    (Fallback::Ignore, _) => true,
+                    (Fallback::Rerender, Some(source)) => match meta {
+                        Some(meta) => {
+                            self.routes
+                                .rerender(&meta.scene_id, source, ctx, vp, d)
+                                .await?
+                        }
+                        None => false,
+                    },
+                    (Fallback::Rerender, None) => false,
+                    (Fallback::SwitchTo(id), _) => {
+                        self.routes.switch_to_scene_by_id(id, ctx, vp, d).await?
+                            != Switched::Unknown
+                    }
+                    (Fallback::Alert(text), _) => {
+                        if let Err(e) = ctx
+                            .bot()
+                            .answer_callback_query(q.id.clone())
+                            .text(text)
+                            .show_alert(true)
+                            .await
+                        {
+                            tracing::warn!(error=?e, "answer_callback_query failed (unhandled alert)");
+                        }
+
+                        return Ok(());
+                    }
+                };
+
+                if !handled {
+                    answer_stale(ctx, q).await;
+                } else if let Err(e) = ctx.bot().answer_callback_query(q.id.clone()).await {
+                    tracing::warn!(error=?e, "answer_callback_query failed (unhandled)");
+                }
+            }
+            AppEvent::InlineQuery(q) => {
+                if !self.routes.handle_inline_query(ctx, vp, q).await?
+                    && let Err(e) = ctx.bot().answer_inline_query(q.id.clone(), []).await
+                {
+                    tracing::warn!(error=?e, "answer_inline_query failed (no scene)");
+                }
+            }
+            AppEvent::ChosenInlineResult(r) => {
+                if let Some(inline_id) = r.inline_message_id.as_deref()
+                    && !vp.bind_inline_result(&r.result_id, inline_id).await?
+                {
+                    tracing::debug!(result_id=%r.result_id, "chosen inline result not bound");
+                }
+            }
+            AppEvent::MyChatMember(u) => {
+                let reachable = u.new_chat_member.kind.is_present();
+                vp.set_unreachable(ctx.chat(), !reachable).await;
+
+                if let Some(hook) = &self.member_hook {
+                    hook(MemberEvent::Bot {
+                        update: u.clone(),
+                        reachable,
+                    })
+                    .await;
+                }
+            }
+            AppEvent::ChatMember(u) => {
+                if let Some(hook) = &self.member_hook {
+                    hook(MemberEvent::User(u.clone())).await;
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Deliver an event claimed via [`Viewport::take_due_events`];
+    /// `ctx` and `d` must address the event's chat.
+    #[tracing::instrument(
+        name = "router.deliver",
+        skip(self, ctx, vp, d, ev),
+        fields(chat_id = %ev.chat_id, scene_id = %ev.scene_id)
+    )]
+    pub async fn deliver<C, D, S, M>(
+        &self,
+        ctx: &C,
+        vp: &Viewport<M>,
+        d: &Dialogue<D, S>,
+        ev: &ScheduledEvent,
+    ) -> anyhow::Result<bool>
+    where
+        C: AppCtx + Send + Sync,
+        D: UiStore + Send + Sync,
+        S: UiDialogueStorage<D>,
+        M: Store + Send + Sync,
+        R: compose::RouterDispatch<C, D, S, M>,
+        <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    {
+        #[cfg(feature = "metrics")]
+        {
+            crate::metrics::router_handle("scheduled", ctx.chat().0, ctx.user_id());
+        }
+
+        self.routes.handle_scheduled(ctx, vp, d, ev).await
+    }
+
+    async fn over_limit<C, M>(&self, ctx: &C, vp: &Viewport<M>) -> Option<&'static str>
+    where
+        C: AppCtx,
+        M: Store + Send + Sync,
+    {
+        if let Some(limit) = self.user_limit
+            && !vp.take_token(&format!("u:{}", ctx.user_id()), limit).await
+        {
+            return Some("user");
+        }
+
+        if let Some(limit) = self.chat_limit
+            && !vp.take_token(&format!("c:{}", ctx.chat().0), limit).await
+        {
+            return Some("chat");
+        }
+
+        None
+    }
+
+    async fn answer_throttled<C: AppCtx>(&self, ctx: &C, q: &CallbackQuery) {
+        if let Err(e) = ctx
+            .bot()
+            .answer_callback_query(q.id.clone())
+            .text(self.throttle_text.clone())
+            .await
+        {
+            tracing::warn!(error=?e, "answer_callback_query failed (throttled)");
+        }
+    }
+
+    // Hook verdict for an unhandled event and the meta it was given
+    async fn fallback<M: Store + Send + Sync>(
+        &self,
+        ev: &AppEvent<'_>,
+        vp: &Viewport<M>,
+        source: Option<(ChatId, MessageId)>,
+    ) -> (Fallback, Option<MessageMeta>) {
+        let Some(hook) = &self.fallback else {
+            return (Fallback::Default, None);
+        };
+        let meta = match source {
+            Some((chat, mid)) => vp.load_meta(chat, mid.0).await.ok().flatten(),
+            None => None,
+        };
+
+        (hook(ev, meta.as_ref()), meta)
+    }
+}
+
+async fn answer_stale<C: AppCtx>(ctx: &C, q: &CallbackQuery) {
+    if let Err(e) = ctx
+        .bot()
+        .answer_callback_query(q.id.clone())
+        .text("This menu is no longer active, enter /start command and open this section again.")
+        .show_alert(true)
+        .await
+    {
+        tracing::warn!(error=?e, "answer_callback_query failed (unknown fallback)");
+    }
 }
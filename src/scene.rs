@@ -1,21 +1,188 @@
-use teloxide::types::{InlineKeyboardMarkup, ParseMode};
+use std::future::Future;
+use std::pin::Pin;
+use teloxide::types::{ParseMode, ReplyMarkup};
+
+pub type SceneFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+#[cfg(feature = "derive")]
+pub use telegram_botkit_derive::ActionCodec;
+
+/// Telegram's limit for `callback_data`.
+pub const CALLBACK_DATA_MAX: usize = 64;
 
 pub trait ActionCodec: Sized {
+    /// Worst-case `encode` length without the prefix;
+    /// `None` when unknown (hand-written impls).
+    const MAX_ENCODED_LEN: Option<usize> = None;
+
     fn encode(&self, prefix: &str) -> String;
     fn decode(prefix: &str, s: &str) -> Option<Self>;
 }
 
-#[derive(Clone, Debug)]
+/// Fails to compile when `S`'s events may encode
+/// past [`CALLBACK_DATA_MAX`] with its prefix. Signed
+/// data pushed over the limit is sent as a token.
+pub const fn assert_codec_fits<S: Scene>()
+where
+    S::Event: ActionCodec,
+{
+    if let Some(n) = <S::Event as ActionCodec>::MAX_ENCODED_LEN {
+        assert!(
+            S::PREFIX.len() + n <= CALLBACK_DATA_MAX,
+            "scene events may exceed Telegram's 64-byte callback limit"
+        );
+    }
+}
+
+/// Percent-escape `:`, `%` and non-ASCII bytes
+/// so a string can sit in a `:`-separated action.
+pub fn escape_field(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b':' | b'%' | 0x80.. => out.push_str(&format!("%{b:02X}")),
+            _ => out.push(b as char),
+        }
+    }
+
+    out
+}
+
+/// Cut `s` to at most `max` bytes on a char boundary,
+/// matching the `max_len` check on decode.
+pub fn truncate_field(s: &str, max: usize) -> &str {
+    let mut end = max.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+/// Inverse of [`escape_field`].
+pub fn unescape_field(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+
+    String::from_utf8(out).ok()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChatKind {
+    #[default]
+    Private,
+    Group,
+    Supergroup,
+    Channel,
+}
+
+impl ChatKind {
+    pub fn of(chat: &teloxide::types::Chat) -> Self {
+        if chat.is_channel() {
+            ChatKind::Channel
+        } else if chat.is_supergroup() {
+            ChatKind::Supergroup
+        } else if chat.is_group() {
+            ChatKind::Group
+        } else {
+            ChatKind::Private
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct Ctx {
     pub user_id: i64,
+    pub chat_id: i64,
+    pub chat_kind: ChatKind,
+    pub language_code: Option<String>,
+    pub username: Option<String>,
+    /// Unix seconds, fixed for the whole event.
+    pub now: i64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct View {
+    /// Message text, or the caption when `media` is set.
     pub text: String,
-    pub markup: Option<InlineKeyboardMarkup>,
+    pub markup: Option<ReplyMarkup>,
     pub parse_mode: Option<ParseMode>,
     pub disable_web_page_preview: Option<bool>,
+    pub media: Option<Media>,
+}
+
+impl View {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Inline keyboards, reply keyboards,
+    /// `ForceReply` and `ReplyKeyboardRemove`.
+    pub fn with_markup(mut self, markup: impl Into<ReplyMarkup>) -> Self {
+        self.markup = Some(markup.into());
+        self
+    }
+
+    /// Send `media` with the text as its caption.
+    pub fn with_media(mut self, media: Media) -> Self {
+        self.media = Some(media);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Photo,
+    Animation,
+    Document,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MediaSource {
+    FileId(String),
+    Url(url::Url),
+    Bytes { file_name: String, data: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Media {
+    pub kind: MediaKind,
+    pub source: MediaSource,
+}
+
+impl Media {
+    pub fn photo(source: MediaSource) -> Self {
+        Self {
+            kind: MediaKind::Photo,
+            source,
+        }
+    }
+
+    pub fn animation(source: MediaSource) -> Self {
+        Self {
+            kind: MediaKind::Animation,
+            source,
+        }
+    }
+
+    pub fn document(source: MediaSource) -> Self {
+        Self {
+            kind: MediaKind::Document,
+            source,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -25,15 +192,48 @@ pub enum RenderPolicy {
     EditOnly,
 }
 
-#[derive(Clone, Debug)]
-pub enum Effect<S> {
+/// Next step after an update; `E` is the scene's event type.
+#[derive(Debug)]
+pub enum Effect<S, E> {
     Stay(S, RenderPolicy),
     StayWithEffect(S, RenderPolicy, Vec<UiEffect>),
+    /// Render (and answer the callback), then run the command
+    /// and feed its event back through [`Scene::update_async`].
+    StayWithCmd(S, RenderPolicy, Cmd<E>),
     SwitchScene(SceneSwitch),
+    /// Remember the current scene in
+    /// this state, then switch.
+    Push(S, SceneSwitch),
+    /// Return to the last pushed scene.
+    Pop,
     Noop,
     NoopWithEffect(Vec<UiEffect>),
 }
 
+/// Deferred job yielding a follow-up scene event.
+pub struct Cmd<E> {
+    fut: SceneFuture<'static, E>,
+}
+
+impl<E> Cmd<E> {
+    pub fn perform<F>(fut: F) -> Self
+    where
+        F: Future<Output = E> + Send + 'static,
+    {
+        Self { fut: Box::pin(fut) }
+    }
+
+    pub async fn run(self) -> E {
+        self.fut.await
+    }
+}
+
+impl<E> std::fmt::Debug for Cmd<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cmd(..)")
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum UiEffect {
     Notification {
@@ -41,17 +241,113 @@ pub enum UiEffect {
         ttl_secs: Option<u64>,
     },
     ClearPrompt,
+    /// Deliver an encoded scene event to the
+    /// current message after a delay.
+    Schedule {
+        delay_secs: u64,
+        payload: String,
+    },
+}
+
+impl UiEffect {
+    /// Needs a store with timer support and the
+    /// route registered with `SceneBuilder::codec`.
+    pub fn schedule<S: Scene>(event: &S::Event, delay: std::time::Duration) -> Self
+    where
+        S::Event: ActionCodec,
+    {
+        const { assert_codec_fits::<S>() };
+
+        UiEffect::Schedule {
+            delay_secs: delay.as_secs(),
+            payload: event.encode(S::PREFIX),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct SceneSwitch {
     pub to_scene_id: &'static str,
+    pub params: Option<serde_json::Value>,
+}
+
+impl SceneSwitch {
+    pub fn to(to_scene_id: &'static str) -> Self {
+        Self {
+            to_scene_id,
+            params: None,
+        }
+    }
+
+    /// Attach params handed to the
+    /// target's [`Scene::init_with`].
+    pub fn with_params<P: serde::Serialize>(mut self, params: &P) -> Self {
+        match serde_json::to_value(params) {
+            Ok(v) => self.params = Some(v),
+            Err(e) => {
+                tracing::warn!(error=?e, to=%self.to_scene_id, "scene switch params serialize failed");
+            }
+        }
+
+        self
+    }
 }
 
 pub enum MsgPattern {
     AnyText,
+    /// `/name args`, also `/name@bot` in groups.
     Command(&'static str),
+    /// Compiled once by `Builder::build`.
     Regex(&'static str),
+    /// Exact text of a reply-keyboard button.
+    Button(&'static str),
+    // Media kinds are gated by an active prompt like `AnyText`
+    Photo,
+    Document,
+    Voice,
+    Location,
+    Contact,
+    Sticker,
+}
+
+/// Article answering an inline query; the message
+/// it sends is the scene rendered in `state`.
+pub struct InlineResult<St> {
+    pub title: String,
+    pub description: Option<String>,
+    pub state: St,
+}
+
+/// What a [`MsgPattern`] matched, handed to `MsgBinding::to_event`.
+#[derive(Debug, Default)]
+pub struct MsgMatch<'a> {
+    args: &'a str,
+    captures: Option<regex::Captures<'a>>,
+}
+
+impl<'a> MsgMatch<'a> {
+    pub(crate) fn new(args: &'a str, captures: Option<regex::Captures<'a>>) -> Self {
+        Self { args, captures }
+    }
+
+    /// Text after the command, trimmed; the whole
+    /// text (or caption) for other patterns.
+    pub fn args(&self) -> &'a str {
+        self.args
+    }
+
+    pub fn argv(&self) -> impl Iterator<Item = &'a str> {
+        self.args.split_whitespace()
+    }
+
+    /// Regex group by index, 0 is the whole match.
+    pub fn group(&self, i: usize) -> Option<&'a str> {
+        Some(self.captures.as_ref()?.get(i)?.as_str())
+    }
+
+    pub fn name(&self, name: &str) -> Option<&'a str> {
+        Some(self.captures.as_ref()?.name(name)?.as_str())
+    }
 }
 
 pub enum CbKey {
@@ -59,14 +355,59 @@ pub enum CbKey {
     Prefix(&'static str),
 }
 
+impl CbKey {
+    pub fn matches(&self, data: &str) -> bool {
+        match *self {
+            CbKey::Exact(k) => data == k,
+            CbKey::Prefix(p) => data.starts_with(p),
+        }
+    }
+}
+
 pub struct MsgBinding<E> {
     pub pattern: MsgPattern,
-    pub to_event: fn(&teloxide::types::Message) -> Option<E>,
+    pub to_event: fn(&teloxide::types::Message, &MsgMatch<'_>) -> Option<E>,
+}
+
+impl<E> MsgBinding<E> {
+    pub fn new(
+        pattern: MsgPattern,
+        to_event: fn(&teloxide::types::Message, &MsgMatch<'_>) -> Option<E>,
+    ) -> Self {
+        Self { pattern, to_event }
+    }
 }
 
 pub struct CbBinding<E> {
     pub key: CbKey,
     pub to_event: fn(&teloxide::types::CallbackQuery) -> Option<E>,
+    /// Minimum time between presses per user; faster
+    /// presses get the router's throttle toast.
+    pub cooldown: Option<std::time::Duration>,
+}
+
+impl<E> CbBinding<E> {
+    pub fn new(key: CbKey, to_event: fn(&teloxide::types::CallbackQuery) -> Option<E>) -> Self {
+        Self {
+            key,
+            to_event,
+            cooldown: None,
+        }
+    }
+
+    pub fn with_cooldown(mut self, cooldown: std::time::Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Event for `q` if its data matches `key`.
+    pub fn event(&self, q: &teloxide::types::CallbackQuery) -> Option<E> {
+        if !self.key.matches(q.data.as_deref()?) {
+            return None;
+        }
+
+        (self.to_event)(q)
+    }
 }
 
 pub struct Bindings<E> {
@@ -74,6 +415,15 @@ pub struct Bindings<E> {
     pub cb: Vec<CbBinding<E>>,
 }
 
+impl<E> Default for Bindings<E> {
+    fn default() -> Self {
+        Self {
+            msg: vec![],
+            cb: vec![],
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Snapshot<'a> {
     pub scene_version: u16,
@@ -88,15 +438,62 @@ pub trait Scene: Send + Sync + 'static {
     const PREFIX: &'static str;
 
     type State: serde::de::DeserializeOwned + serde::Serialize + Send + Clone + Eq + std::fmt::Debug;
-    type Event: Clone + Send + std::fmt::Debug;
+    type Event: Clone + Send + std::fmt::Debug + 'static;
 
     fn init(&self, _ctx: &Ctx) -> Self::State;
 
+    /// Initial state when entered through a switch
+    /// carrying params. Defaults to [`Scene::init`].
+    fn init_with(&self, ctx: &Ctx, _params: &serde_json::Value) -> Self::State {
+        self.init(ctx)
+    }
+
     fn render(&self, _ctx: &Ctx, _state: &Self::State) -> View;
 
-    fn update(&self, _ctx: &Ctx, state: &Self::State, _event: Self::Event) -> Effect<Self::State>;
+    /// Sync update. Scenes with I/O override [`Scene::update_async`]
+    /// and return `Effect::Noop` here.
+    fn update(
+        &self,
+        ctx: &Ctx,
+        state: &Self::State,
+        event: Self::Event,
+    ) -> Effect<Self::State, Self::Event>;
+
+    /// Called by the router for every event.
+    /// Defaults to the sync [`Scene::update`].
+    fn update_async<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+        state: Self::State,
+        event: Self::Event,
+    ) -> SceneFuture<'a, Effect<Self::State, Self::Event>> {
+        Box::pin(async move { self.update(ctx, &state, event) })
+    }
+
+    /// Explicit bindings; scenes routed with
+    /// `SceneBuilder::codec` can skip callbacks.
+    fn bindings(&self) -> Bindings<Self::Event> {
+        Bindings::default()
+    }
+
+    /// Runs after the scene is entered by
+    /// a switch, push or back navigation.
+    fn on_enter(&self, _ctx: &Ctx, _state: &Self::State) -> Vec<UiEffect> {
+        vec![]
+    }
+
+    /// Inline mode results; `None` leaves the query
+    /// to the next scene. Chosen results stay
+    /// interactive when inline feedback is enabled.
+    fn inline_query(&self, _ctx: &Ctx, _query: &str) -> Option<Vec<InlineResult<Self::State>>> {
+        None
+    }
 
-    fn bindings(&self) -> Bindings<Self::Event>;
+    /// Runs with the last state before another
+    /// scene replaces this one and renders.
+    fn on_leave(&self, _ctx: &Ctx, _state: &Self::State) -> Vec<UiEffect> {
+        vec![]
+    }
 
     fn snapshot(&self, state: &Self::State) -> (Option<String>, Option<String>) {
         match serde_json::to_string(state) {
@@ -122,6 +519,18 @@ pub trait Scene: Send + Sync + 'static {
             }
         }
 
+        if snap.scene_version != Self::VERSION {
+            let value = serde_json::from_str::<serde_json::Value>(json).ok()?;
+            return self.migrate(snap.scene_version, value);
+        }
+
         serde_json::from_str::<Self::State>(json).ok()
     }
+
+    /// Upgrade a snapshot written by an older `VERSION`. The default
+    /// keeps snapshots that still deserialize; `None` falls back
+    /// to [`Scene::init`].
+    fn migrate(&self, _from_version: u16, state: serde_json::Value) -> Option<Self::State> {
+        serde_json::from_value(state).ok()
+    }
 }
@@ -1,7 +1,17 @@
 use std::collections::HashMap;
 use teloxide::dispatching::dialogue;
+use teloxide::types::ChatId;
 
 const MAX_SCENE_MAPPINGS: usize = 128;
+const MAX_NAV_DEPTH: usize = 16;
+
+/// Scene to return to on `ui:back`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct NavEntry {
+    pub scene_id: String,
+    pub scene_version: u16,
+    pub state_json: Option<String>,
+}
 
 pub trait UiStore:
     Clone
@@ -29,6 +39,27 @@ pub trait UiStore:
 
     fn ui_get_active_scene_id(&self) -> Option<String>;
     fn ui_set_active_scene_id(&mut self, id: Option<String>);
+
+    fn ui_push_nav_entry(&mut self, entry: NavEntry);
+    fn ui_pop_nav_entry(&mut self) -> Option<NavEntry>;
+    fn ui_get_nav_depth(&self) -> usize;
+}
+
+/// Dialogue id for `user` in `chat`: private chats keep their id,
+/// groups get one per member, e.g. `Dialogue::new(storage, dialogue_key(chat, user))`.
+pub fn dialogue_key(chat: ChatId, user_id: i64) -> ChatId {
+    if chat.0 > 0 {
+        return chat;
+    }
+
+    let mut h = blake3::Hasher::new();
+    h.update(&chat.0.to_le_bytes());
+    h.update(&user_id.to_le_bytes());
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&h.finalize().as_bytes()[..8]);
+
+    // Far below any id Telegram hands out
+    ChatId(i64::MIN + (u64::from_le_bytes(b) >> 2) as i64)
 }
 
 pub trait UiDialogueStorage<D>: dialogue::Storage<D> + Send + Sync
@@ -51,6 +82,8 @@ pub struct SimpleSession {
     input_prompt_message_id: Option<i32>,
     reply_to_last_once: bool,
     message_scenes: HashMap<i32, String>,
+    #[serde(default)]
+    nav_stack: Vec<NavEntry>,
 }
 
 impl UiStore for SimpleSession {
@@ -111,4 +144,22 @@ impl UiStore for SimpleSession {
     fn ui_set_active_scene_id(&mut self, id: Option<String>) {
         self.active_scene_id = id;
     }
+
+    fn ui_push_nav_entry(&mut self, entry: NavEntry) {
+        self.nav_stack.push(entry);
+
+        // Drop the oldest entries
+        if self.nav_stack.len() > MAX_NAV_DEPTH {
+            let excess = self.nav_stack.len() - MAX_NAV_DEPTH;
+            self.nav_stack.drain(..excess);
+        }
+    }
+
+    fn ui_pop_nav_entry(&mut self) -> Option<NavEntry> {
+        self.nav_stack.pop()
+    }
+
+    fn ui_get_nav_depth(&self) -> usize {
+        self.nav_stack.len()
+    }
 }
@@ -6,6 +6,72 @@ pub const BACK: &str = "ui:back";
 pub const CANCEL: &str = "ui:cancel";
 pub const HIDE: &str = "ui:hide";
 pub const DISABLE_NOTIFICATIONS: &str = "ui:disable_notifications";
+/// Prefix of tokens standing in for oversized
+/// payloads, see `Viewport::with_payload_tokens`.
+pub const PAYLOAD_TOKEN: &str = "ui:p:";
+/// Prefix of inline query result ids.
+pub const INLINE_RESULT: &str = "ui:r:";
+/// Prefix of built-in callbacks, never signed.
+pub const UI_PREFIX: &str = "ui:";
+
+const SIGNATURE_SEP: char = '~';
+const SIGNATURE_HEX: usize = 16;
+
+/// Keyed blake3 MAC over callback data, appended as `{data}~{tag}`.
+#[derive(Clone)]
+pub struct CallbackSigner {
+    key: [u8; 32],
+    token_key: [u8; 32],
+}
+
+impl CallbackSigner {
+    /// Bytes a signature adds to callback data.
+    pub const OVERHEAD: usize = 1 + SIGNATURE_HEX;
+
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: blake3::derive_key("telegram-botkit callback data v1", secret.as_ref()),
+            token_key: blake3::derive_key("telegram-botkit payload token v1", secret.as_ref()),
+        }
+    }
+
+    /// Keyed hash of a stored payload, so tokens
+    /// can't be derived from guessed content.
+    pub(crate) fn token_hash(&self, payload: &str) -> String {
+        blake3::keyed_hash(&self.token_key, payload.as_bytes())
+            .to_hex()
+            .to_string()
+    }
+
+    /// Built-in `ui:` callbacks stay unsigned.
+    pub fn needs_signature(data: &str) -> bool {
+        !data.starts_with(UI_PREFIX)
+    }
+
+    pub fn sign(&self, data: &str) -> String {
+        format!("{data}{SIGNATURE_SEP}{}", self.tag(data))
+    }
+
+    /// Data without its signature, `None` when missing or forged.
+    pub fn verify<'a>(&self, signed: &'a str) -> Option<&'a str> {
+        let (data, tag) = signed.rsplit_once(SIGNATURE_SEP)?;
+        let expected = self.tag(data);
+
+        // Constant-time compare
+        let diff = tag.len() ^ expected.len();
+        let diff = tag
+            .bytes()
+            .zip(expected.bytes())
+            .fold(diff, |acc, (a, b)| acc | (a ^ b) as usize);
+
+        (diff == 0).then_some(data)
+    }
+
+    fn tag(&self, data: &str) -> String {
+        let mac = blake3::keyed_hash(&self.key, data.as_bytes());
+        hex::encode(&mac.as_bytes()[..SIGNATURE_HEX / 2])
+    }
+}
 
 pub async fn answer_callback_safe<R: UiRequester>(bot: &R, q: &CallbackQuery) {
     if let Err(e) = bot.answer_callback_query(q.id.clone()).await {
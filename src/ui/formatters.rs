@@ -287,13 +287,13 @@ pub fn format_duration_short(secs: u64) -> String {
     const HOUR: u64 = 3_600;
     const MIN: u64 = 60;
 
-    if secs % DAY == 0 {
+    if secs.is_multiple_of(DAY) {
         return format!("{}d", secs / DAY);
     }
-    if secs % HOUR == 0 {
+    if secs.is_multiple_of(HOUR) {
         return format!("{}h", secs / HOUR);
     }
-    if secs % MIN == 0 {
+    if secs.is_multiple_of(MIN) {
         return format!("{}m", secs / MIN);
     }
 
@@ -1,9 +1,22 @@
-use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use crate::scene::{ActionCodec, Scene};
+
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup};
 
 pub fn back_button(text: &str, data: &str) -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(text, data)]])
 }
 
+/// Callback button carrying `event` encoded with
+/// `S::PREFIX`, as decoded by `SceneBuilder::codec`.
+pub fn event_button<S: Scene>(text: &str, event: &S::Event) -> InlineKeyboardButton
+where
+    S::Event: ActionCodec,
+{
+    const { crate::scene::assert_codec_fits::<S>() };
+
+    InlineKeyboardButton::callback(text, event.encode(S::PREFIX))
+}
+
 pub fn rows(rows: Vec<Vec<(&str, &str)>>) -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::new(
         rows.into_iter()
@@ -16,6 +29,17 @@ pub fn rows(rows: Vec<Vec<(&str, &str)>>) -> InlineKeyboardMarkup {
     )
 }
 
+/// Persistent bottom keyboard; presses arrive as
+/// text, bind them with `MsgPattern::Button`.
+pub fn reply_rows(rows: Vec<Vec<&str>>) -> KeyboardMarkup {
+    KeyboardMarkup::new(
+        rows.into_iter()
+            .map(|r| r.into_iter().map(KeyboardButton::new).collect::<Vec<_>>())
+            .collect::<Vec<Vec<KeyboardButton>>>(),
+    )
+    .resize_keyboard()
+}
+
 pub fn to_row(items: Vec<(String, String)>) -> Vec<InlineKeyboardButton> {
     items
         .into_iter()
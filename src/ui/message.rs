@@ -4,12 +4,14 @@ use crate::ui::prelude::UiRequester;
 use dispatching::dialogue::Dialogue;
 use std::time::Duration;
 use teloxide::payloads::{EditMessageTextSetters, SendMessageSetters};
-use teloxide::types::{ChatId, InlineKeyboardMarkup, LinkPreviewOptions, MessageId, ParseMode};
+use teloxide::types::{
+    ChatId, InlineKeyboardMarkup, LinkPreviewOptions, MessageId, ParseMode, ReplyMarkup,
+};
 use teloxide::{dispatching, requests};
 
 #[derive(Default, Clone)]
 pub struct ReplyOptions {
-    pub markup: Option<InlineKeyboardMarkup>,
+    pub markup: Option<ReplyMarkup>,
     pub parse_mode: Option<ParseMode>,
     pub disable_web_page_preview: Option<bool>,
 }
@@ -38,15 +40,15 @@ where
     S: UiDialogueStorage<D>,
     <S as dispatching::dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
 {
-    if let Some(prev) = previous {
-        if let Err(e) = bot.delete_message(chat, prev).await {
-            tracing::debug!(
-                error=?e,
-                chat=%chat.0,
-                prev=%prev.0,
-                "delete previous message failed",
-            );
-        }
+    if let Some(prev) = previous
+        && let Err(e) = bot.delete_message(chat, prev).await
+    {
+        tracing::debug!(
+            error=?e,
+            chat=%chat.0,
+            prev=%prev.0,
+            "delete previous message failed",
+        );
     }
 
     let mut req = bot.send_message(chat, text.into());
@@ -59,16 +61,16 @@ where
         req = req.reply_markup(markup);
     }
 
-    if let Some(disable) = opts.disable_web_page_preview {
-        if disable {
-            req = req.link_preview_options(LinkPreviewOptions {
-                is_disabled: true,
-                url: None,
-                prefer_small_media: false,
-                prefer_large_media: false,
-                show_above_text: false,
-            });
-        }
+    if let Some(disable) = opts.disable_web_page_preview
+        && disable
+    {
+        req = req.link_preview_options(LinkPreviewOptions {
+            is_disabled: true,
+            url: None,
+            prefer_small_media: false,
+            prefer_large_media: false,
+            show_above_text: false,
+        });
     }
 
     let msg = req.await?;
@@ -127,10 +129,10 @@ where
 {
     let mut to_mid: Option<MessageId> = None;
 
-    if let Ok(s) = d.get_or_default().await {
-        if let Some(last) = s.ui_get_last_action_message_id() {
-            to_mid = Some(MessageId(last));
-        }
+    if let Ok(s) = d.get_or_default().await
+        && let Some(last) = s.ui_get_last_action_message_id()
+    {
+        to_mid = Some(MessageId(last));
     }
 
     let text_owned: String = text.into();
@@ -146,16 +148,16 @@ where
             req = req.parse_mode(pm);
         }
 
-        if let Some(disable) = opts.disable_web_page_preview {
-            if disable {
-                req = req.link_preview_options(LinkPreviewOptions {
-                    is_disabled: true,
-                    url: None,
-                    prefer_small_media: false,
-                    prefer_large_media: false,
-                    show_above_text: false,
-                });
-            }
+        if let Some(disable) = opts.disable_web_page_preview
+            && disable
+        {
+            req = req.link_preview_options(LinkPreviewOptions {
+                is_disabled: true,
+                url: None,
+                prefer_small_media: false,
+                prefer_large_media: false,
+                show_above_text: false,
+            });
         }
 
         match req.await {
@@ -183,16 +185,16 @@ where
         req = req.parse_mode(pm);
     }
 
-    if let Some(disable) = opts.disable_web_page_preview {
-        if disable {
-            req = req.link_preview_options(LinkPreviewOptions {
-                is_disabled: true,
-                url: None,
-                prefer_small_media: false,
-                prefer_large_media: false,
-                show_above_text: false,
-            });
-        }
+    if let Some(disable) = opts.disable_web_page_preview
+        && disable
+    {
+        req = req.link_preview_options(LinkPreviewOptions {
+            is_disabled: true,
+            url: None,
+            prefer_small_media: false,
+            prefer_large_media: false,
+            show_above_text: false,
+        });
     }
 
     let msg = req.await?;
@@ -252,26 +254,26 @@ where
     S: UiDialogueStorage<D>,
     <S as dispatching::dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
 {
-    if let Ok(mut s) = d.get_or_default().await {
-        if let Some(mid) = s.ui_get_input_prompt_message_id() {
-            if let Err(e) = bot.delete_message(chat, MessageId(mid)).await {
-                tracing::debug!(
-                    error=?e,
-                    chat=%chat.0,
-                    mid=%mid,
-                    "delete prompt failed",
-                );
-            }
+    if let Ok(mut s) = d.get_or_default().await
+        && let Some(mid) = s.ui_get_input_prompt_message_id()
+    {
+        if let Err(e) = bot.delete_message(chat, MessageId(mid)).await {
+            tracing::debug!(
+                error=?e,
+                chat=%chat.0,
+                mid=%mid,
+                "delete prompt failed",
+            );
+        }
 
-            s.ui_set_input_prompt_message_id(None);
+        s.ui_set_input_prompt_message_id(None);
 
-            if let Err(e) = d.update(s).await {
-                tracing::error!(
-                    error=?e,
-                    chat=%chat.0,
-                    "dialogue update failed (clear_input_prompt_message)",
-                );
-            }
+        if let Err(e) = d.update(s).await {
+            tracing::error!(
+                error=?e,
+                chat=%chat.0,
+                "dialogue update failed (clear_input_prompt_message)",
+            );
         }
     }
 }
@@ -294,6 +296,24 @@ where
     }
 }
 
+/// Errors meaning the chat can't receive
+/// messages until it adds or unblocks the bot.
+pub fn is_unreachable_error(e: &teloxide::RequestError) -> bool {
+    use teloxide::ApiError;
+
+    matches!(
+        e,
+        teloxide::RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::BotKicked
+                | ApiError::BotKickedFromSupergroup
+                | ApiError::BotKickedFromChannel
+                | ApiError::UserDeactivated
+                | ApiError::CantInitiateConversation
+        )
+    )
+}
+
 pub async fn delete_incoming<R: UiRequester>(bot: &R, msg: &teloxide::types::Message) -> bool {
     match bot.delete_message(msg.chat.id, msg.id).await {
         Ok(_) => true,
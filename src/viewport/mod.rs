@@ -3,20 +3,31 @@ pub mod redis;
 pub mod store;
 
 use crate::router::compose::SceneLookup;
-use crate::scene::RenderPolicy;
+use crate::scene::{CALLBACK_DATA_MAX, Media, MediaKind, MediaSource, RenderPolicy, View};
 use crate::session::{UiDialogueStorage, UiStore};
+use crate::ui::callback::{self, CallbackSigner};
 use crate::ui::message;
 use crate::ui::prelude::UiRequester;
 
 use crate::router::core::DIALOGUE_SNAPSHOT_TAG;
 use dialogue::Dialogue;
-use store::Store;
+use std::borrow::Cow;
+use store::{RateLimit, Store};
 use teloxide::{
     dispatching::dialogue,
-    payloads::{EditMessageTextSetters, SendMessageSetters},
+    payloads::{
+        EditMessageCaptionSetters, EditMessageMediaSetters, EditMessageTextInlineSetters,
+        EditMessageTextSetters, SendAnimationSetters, SendDocumentSetters, SendMessageSetters,
+        SendPhotoSetters,
+    },
     prelude::{CallbackQuery, Requester},
     sugar::request::RequestReplyExt,
-    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, LinkPreviewOptions, MessageId},
+    types::{
+        ChatId, FileId, InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup,
+        InlineQueryResult, InlineQueryResultArticle, InputFile, InputMedia, InputMediaAnimation,
+        InputMediaDocument, InputMediaPhoto, InputMessageContent, InputMessageContentText,
+        LinkPreviewOptions, Message, MessageId, ReplyMarkup,
+    },
 };
 use tracing::instrument;
 
@@ -29,6 +40,26 @@ pub struct MessageMeta {
     pub state_checksum: Option<String>,
     pub created_at: i64,
     pub ttl_secs: u32,
+    #[serde(default)]
+    pub owner_id: Option<i64>,
+    /// Fingerprint of the media shown; `None` for text.
+    #[serde(default)]
+    pub media: Option<String>,
+}
+
+/// Scene event due for delivery to a
+/// message, see [`Viewport::take_due_events`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledEvent {
+    pub chat_id: i64,
+    /// User whose update scheduled it, to rebuild
+    /// a per-user dialogue in group mode.
+    #[serde(default)]
+    pub user_id: Option<i64>,
+    pub message_id: i32,
+    pub scene_id: String,
+    pub payload: String,
+    pub due_at: i64,
 }
 
 #[derive(Clone, Debug)]
@@ -38,18 +69,69 @@ pub struct MetaSpec {
     pub state_json: Option<String>,
     pub state_ref: Option<String>,
     pub ttl_secs: u32,
+    /// User the message belongs to, see `RouterBuilder::with_group_mode`.
+    pub owner_id: Option<i64>,
+}
+
+impl MetaSpec {
+    /// No state, kept for [`SNAP_TTL_SECS`].
+    pub fn new(scene_id: &'static str, scene_version: u16) -> Self {
+        Self {
+            scene_id,
+            scene_version,
+            state_json: None,
+            state_ref: None,
+            ttl_secs: SNAP_TTL_SECS,
+            owner_id: None,
+        }
+    }
+
+    pub fn with_owner(mut self, user_id: i64) -> Self {
+        self.owner_id = Some(user_id);
+        self
+    }
 }
 
 #[derive(Clone)]
 pub struct Viewport<M: Store> {
     meta: M,
+    // Fallback for what `meta` doesn't keep
+    local: store::MemoryStore,
+    payload_tokens: bool,
+    signer: Option<CallbackSigner>,
 }
 
 pub const SNAP_TTL_SECS: u32 = 3 * 24 * 60 * 60;
+/// Unchosen inline results; outlives Telegram's
+/// default 300s cache of inline answers.
+const INLINE_RESULT_TTL_SECS: u32 = 10 * 60;
 
 impl<M: Store> Viewport<M> {
     pub fn new(meta: M) -> Self {
-        Self { meta }
+        Self {
+            meta,
+            local: store::MemoryStore::default(),
+            payload_tokens: false,
+            signer: None,
+        }
+    }
+
+    /// Store callback data over 64 bytes and send a
+    /// short token instead; needs payload support in `M`.
+    /// Signed data that outgrows the limit always does.
+    pub fn with_payload_tokens(mut self, on: bool) -> Self {
+        self.payload_tokens = on;
+        self
+    }
+
+    /// Sign inline callback data; the router needs
+    /// the same secret, see `RouterBuilder::with_callback_secret`.
+    /// Data over 47 bytes only fits behind a payload token, so `M`
+    /// needs payload support; otherwise it is sent unsigned and
+    /// the router rejects the press.
+    pub fn with_callback_secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.signer = Some(CallbackSigner::new(secret));
+        self
     }
 
     #[instrument(name = "viewport.load_meta", skip(self))]
@@ -63,7 +145,48 @@ impl<M: Store> Viewport<M> {
         bot: &R,
         chat: ChatId,
         d: &Dialogue<D, S>,
-        view: &crate::scene::View,
+        view: &View,
+        policy: RenderPolicy,
+        meta: Option<MetaSpec>,
+    ) -> anyhow::Result<()>
+    where
+        R: UiRequester,
+        <R as Requester>::SendMessage: Send,
+        <R as Requester>::EditMessageText: Send,
+        <R as Requester>::DeleteMessage: Send,
+        <R as Requester>::SendPhoto: Send,
+        <R as Requester>::SendAnimation: Send,
+        <R as Requester>::SendDocument: Send,
+        <R as Requester>::EditMessageMedia: Send,
+        <R as Requester>::EditMessageCaption: Send,
+        D: UiStore + Send + Sync,
+        S: UiDialogueStorage<D>,
+        <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+    {
+        // Blocked chats only get renders again once they write
+        if self.is_unreachable(chat).await {
+            tracing::debug!(chat=%chat.0, "render skipped, chat unreachable");
+            return Ok(());
+        }
+
+        let res = self.render_view(bot, chat, d, view, policy, meta).await;
+        if let Err(e) = &res
+            && let Some(e) = e.downcast_ref::<teloxide::RequestError>()
+            && message::is_unreachable_error(e)
+        {
+            tracing::info!(chat=%chat.0, "chat unreachable, renders paused");
+            self.set_unreachable(chat, true).await;
+        }
+
+        res
+    }
+
+    async fn render_view<R, D, S>(
+        &self,
+        bot: &R,
+        chat: ChatId,
+        d: &Dialogue<D, S>,
+        view: &View,
         policy: RenderPolicy,
         meta: Option<MetaSpec>,
     ) -> anyhow::Result<()>
@@ -72,11 +195,17 @@ impl<M: Store> Viewport<M> {
         <R as Requester>::SendMessage: Send,
         <R as Requester>::EditMessageText: Send,
         <R as Requester>::DeleteMessage: Send,
+        <R as Requester>::SendPhoto: Send,
+        <R as Requester>::SendAnimation: Send,
+        <R as Requester>::SendDocument: Send,
+        <R as Requester>::EditMessageMedia: Send,
+        <R as Requester>::EditMessageCaption: Send,
         D: UiStore + Send + Sync,
         S: UiDialogueStorage<D>,
         <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
     {
         let mut mid_opt: Option<MessageId> = None;
+        let view = &*self.seal_callbacks(view).await?;
 
         #[cfg(feature = "metrics")]
         {
@@ -88,15 +217,72 @@ impl<M: Store> Viewport<M> {
             crate::metrics::apply_view(pol);
         }
 
-        match policy {
-            RenderPolicy::EditOrReply => {
+        let last = match d.get_or_default().await {
+            Ok(s) => s.ui_get_last_action_message_id().map(MessageId),
+            Err(_) => None,
+        };
+        let last_media = match last {
+            Some(mid) => self
+                .meta
+                .load(chat, mid.0)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|m| m.media),
+            None => None,
+        };
+
+        // Telegram can't edit a text message into
+        // a media one or back; replace it instead.
+        let kind_changed = last.is_some() && last_media.is_some() != view.media.is_some();
+
+        // Reply keyboards and force-reply
+        // attach only to new messages.
+        let send_only = view
+            .markup
+            .as_ref()
+            .is_some_and(|mk| !matches!(mk, ReplyMarkup::InlineKeyboard(_)));
+
+        match (policy, view.media.as_ref()) {
+            (RenderPolicy::EditOnly, _) if kind_changed => {
+                anyhow::bail!("EditOnly can't switch a message between text and media");
+            }
+            (RenderPolicy::EditOrReply, _) if kind_changed => {
+                mid_opt = Some(send_view(bot, chat, d, last, view).await?);
+            }
+            (RenderPolicy::EditOrReply, _) if send_only => {
+                mid_opt = Some(send_view(bot, chat, d, None, view).await?);
+            }
+            (RenderPolicy::EditOrReply, Some(media)) => {
+                if let Some(mid) = last {
+                    match edit_media(bot, chat, mid, view, media, last_media.as_deref()).await {
+                        Ok(()) => {
+                            mid_opt = Some(mid);
+                        }
+                        Err(e) => {
+                            tracing::debug!(
+                                error=?e,
+                                chat=%chat.0,
+                                mid=%mid.0,
+                                "edit media failed, falling back to send new",
+                            );
+                        }
+                    }
+                }
+
+                if mid_opt.is_none() {
+                    let mid = reply_media(bot, chat, d, None, view, media).await?;
+                    mid_opt = Some(mid);
+                }
+            }
+            (RenderPolicy::EditOrReply, None) => {
                 let mid = message::refresh_or_reply_with(
                     bot,
                     chat,
                     d,
                     view.text.clone(),
                     message::EditOptions {
-                        markup: view.markup.clone(),
+                        markup: inline_markup(view),
                         parse_mode: view.parse_mode,
                         disable_web_page_preview: view.disable_web_page_preview,
                     },
@@ -104,14 +290,37 @@ impl<M: Store> Viewport<M> {
                 .await?;
                 mid_opt = Some(mid);
             }
-            RenderPolicy::EditOnly => {
+            (RenderPolicy::EditOnly, Some(media)) => {
+                if let Some(mid) = last {
+                    match edit_media(bot, chat, mid, view, media, last_media.as_deref()).await {
+                        Ok(()) => {
+                            mid_opt = Some(mid);
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                error=?e,
+                                chat=%chat.0,
+                                mid=%mid.0,
+                                "edit media failed (EditOnly), no fallback"
+                            );
+
+                            if message::is_unreachable_error(&e) {
+                                self.set_unreachable(chat, true).await;
+                            }
+                        }
+                    }
+                } else {
+                    tracing::debug!(chat=%chat.0, "EditOnly but no last_action_message_id; skipping edit");
+                }
+            }
+            (RenderPolicy::EditOnly, None) => {
                 // Edit existing last action
                 // message only; never send new.
                 if let Ok(s) = d.get_or_default().await {
                     if let Some(last) = s.ui_get_last_action_message_id() {
                         let mut req =
                             bot.edit_message_text(chat, MessageId(last), view.text.clone());
-                        if let Some(mk) = view.markup.clone() {
+                        if let Some(mk) = inline_markup(view) {
                             req = req.reply_markup(mk);
                         }
 
@@ -119,16 +328,16 @@ impl<M: Store> Viewport<M> {
                             req = req.parse_mode(pm);
                         }
 
-                        if let Some(disable) = view.disable_web_page_preview {
-                            if disable {
-                                req = req.link_preview_options(LinkPreviewOptions {
-                                    is_disabled: true,
-                                    url: None,
-                                    prefer_small_media: false,
-                                    prefer_large_media: false,
-                                    show_above_text: false,
-                                });
-                            }
+                        if let Some(disable) = view.disable_web_page_preview
+                            && disable
+                        {
+                            req = req.link_preview_options(LinkPreviewOptions {
+                                is_disabled: true,
+                                url: None,
+                                prefer_small_media: false,
+                                prefer_large_media: false,
+                                show_above_text: false,
+                            });
                         }
 
                         match req.await {
@@ -142,6 +351,10 @@ impl<M: Store> Viewport<M> {
                                     mid=%last,
                                     "edit message failed (EditOnly), no fallback"
                                 );
+
+                                if message::is_unreachable_error(&e) {
+                                    self.set_unreachable(chat, true).await;
+                                }
                             }
                         }
                     } else {
@@ -149,63 +362,81 @@ impl<M: Store> Viewport<M> {
                     }
                 }
             }
-            RenderPolicy::SendNew => {
+            (RenderPolicy::SendNew, _) => {
                 // Clear previous prompt and send
                 // a new one with Cancel row if needed.
                 message::clear_input_prompt_message(bot, chat, d).await;
 
-                // Build extras + Cancel row
-                // when markup is empty (prompt).
+                // Build extras + Cancel row when markup
+                // is empty (prompt); force-reply prompts
+                // are answered by replying instead.
                 let is_prompt = match &view.markup {
-                    Some(mk) => mk.inline_keyboard.is_empty(),
+                    Some(ReplyMarkup::InlineKeyboard(mk)) => mk.inline_keyboard.is_empty(),
+                    Some(ReplyMarkup::ForceReply(_)) => true,
+                    Some(_) => false,
                     None => true,
                 };
 
                 if is_prompt {
-                    let mut rows: Vec<Vec<InlineKeyboardButton>> = vec![];
-                    if let Some(mk) = &view.markup {
-                        for r in mk.inline_keyboard.clone().into_iter() {
-                            rows.push(r);
-                        }
-                    }
-
-                    rows.push(vec![InlineKeyboardButton::callback(
-                        "❌ Close",
-                        crate::ui::callback::CANCEL,
-                    )]);
+                    let markup = match &view.markup {
+                        Some(mk @ ReplyMarkup::ForceReply(_)) => mk.clone(),
+                        _ => {
+                            let mut rows: Vec<Vec<InlineKeyboardButton>> = vec![];
+                            if let Some(mk) = inline_markup(view) {
+                                for r in mk.inline_keyboard.into_iter() {
+                                    rows.push(r);
+                                }
+                            }
 
-                    let markup = InlineKeyboardMarkup::new(rows);
+                            rows.push(vec![InlineKeyboardButton::callback(
+                                "❌ Close",
+                                crate::ui::callback::CANCEL,
+                            )]);
 
-                    let mut req = bot.send_message(chat, view.text.clone());
+                            InlineKeyboardMarkup::new(rows).into()
+                        }
+                    };
 
-                    if let Some(pm) = view.parse_mode {
-                        req = req.parse_mode(pm);
+                    // Reply to last action once if requested
+                    let mut reply_to = None;
+                    if let Ok(s) = d.get_or_default().await
+                        && s.ui_get_reply_to_last_once()
+                    {
+                        reply_to = s.ui_get_last_action_message_id().map(MessageId);
                     }
 
-                    if let Some(disable) = view.disable_web_page_preview {
-                        if disable {
-                            req = req.link_preview_options(LinkPreviewOptions {
-                                is_disabled: true,
-                                url: None,
-                                prefer_small_media: false,
-                                prefer_large_media: false,
-                                show_above_text: false,
-                            });
+                    let msg = match &view.media {
+                        Some(media) => {
+                            send_media(bot, chat, view, media, Some(markup), reply_to).await?
                         }
-                    }
+                        None => {
+                            let mut req = bot.send_message(chat, view.text.clone());
 
-                    req = req.reply_markup(markup);
+                            if let Some(pm) = view.parse_mode {
+                                req = req.parse_mode(pm);
+                            }
 
-                    // Reply to last action once if requested
-                    if let Ok(s) = d.get_or_default().await {
-                        if s.ui_get_reply_to_last_once() {
-                            if let Some(to_mid) = s.ui_get_last_action_message_id() {
-                                req = req.reply_to(MessageId(to_mid));
+                            if let Some(disable) = view.disable_web_page_preview
+                                && disable
+                            {
+                                req = req.link_preview_options(LinkPreviewOptions {
+                                    is_disabled: true,
+                                    url: None,
+                                    prefer_small_media: false,
+                                    prefer_large_media: false,
+                                    show_above_text: false,
+                                });
                             }
-                        }
-                    }
 
-                    let msg = req.await?;
+                            req = req.reply_markup(markup);
+
+                            if let Some(to_mid) = reply_to {
+                                req = req.reply_to(to_mid);
+                            }
+
+                            req.await?
+                        }
+                    };
                     let new_id = msg.id;
 
                     if let Ok(mut s) = d.get_or_default().await {
@@ -230,66 +461,41 @@ impl<M: Store> Viewport<M> {
                     // Menu/info flow: clear any prompt and send full keyboard as-is
                     message::clear_input_prompt_message(bot, chat, d).await;
 
-                    let mid = message::compact_reply(
-                        bot,
-                        chat,
-                        d,
-                        None,
-                        view.text.clone(),
-                        message::ReplyOptions {
-                            markup: view.markup.clone(),
-                            parse_mode: view.parse_mode,
-                            disable_web_page_preview: view.disable_web_page_preview,
-                        },
-                        None,
-                    )
-                    .await?;
-
-                    mid_opt = Some(mid);
+                    mid_opt = Some(send_view(bot, chat, d, None, view).await?);
                 }
             }
         }
 
         // Update dialogue mapping and persist
         // meta only if we have a concrete message id.
-        if let Some(spec) = &meta {
-            if let (Some(json), Some(mid)) = (&spec.state_json, mid_opt) {
-                if let Ok(mut s) = d.get_or_default().await {
-                    let checksum = blake3_hex(json.as_bytes());
-                    let env = serde_json::json!({
-                        "_tgk": DIALOGUE_SNAPSHOT_TAG,
-                        "state": json,
-                        "checksum": checksum,
-                    });
-
-                    s.ui_set_scene_for_message(mid.0, env.to_string());
-
-                    if let Err(e) = d.update(s).await {
-                        tracing::error!(
-                            error=?e,
-                            chat=%chat.0,
-                            mid=%mid.0,
-                            "dialogue update failed (apply_view:scene_for_message)",
-                        );
-                    }
-                }
+        if let Some(spec) = &meta
+            && let (Some(json), Some(mid)) = (&spec.state_json, mid_opt)
+            && let Ok(mut s) = d.get_or_default().await
+        {
+            let checksum = blake3_hex(json.as_bytes());
+            let env = serde_json::json!({
+                "_tgk": DIALOGUE_SNAPSHOT_TAG,
+                "state": json,
+                "checksum": checksum,
+                "version": spec.scene_version,
+            });
+
+            s.ui_set_scene_for_message(mid.0, env.to_string());
+
+            if let Err(e) = d.update(s).await {
+                tracing::error!(
+                    error=?e,
+                    chat=%chat.0,
+                    mid=%mid.0,
+                    "dialogue update failed (apply_view:scene_for_message)",
+                );
             }
         }
 
         if let (Some(spec), Some(mid)) = (meta, mid_opt) {
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs() as i64;
-            let checksum = spec.state_json.as_ref().map(|s| blake3_hex(s.as_bytes()));
             let meta = MessageMeta {
-                scene_id: spec.scene_id.to_string(),
-                scene_version: spec.scene_version,
-                state_json: spec.state_json,
-                state_ref: spec.state_ref,
-                state_checksum: checksum,
-                created_at: now,
-                ttl_secs: spec.ttl_secs,
+                media: view.media.as_ref().map(media_key),
+                ..message_meta(spec)
             };
 
             if let Err(e) = self.meta.save(chat, mid.0, meta).await {
@@ -305,12 +511,16 @@ impl<M: Store> Viewport<M> {
         Ok(())
     }
 
+    /// Make the pressed menu the session's current one. Menu
+    /// owners are only honoured in `group_mode`, otherwise
+    /// everyone in the chat shares the menus.
     #[instrument(name = "viewport.activate_from_callback", skip(self, d, q, lookup))]
     pub async fn activate_from_callback<D, S>(
         &self,
         d: &Dialogue<D, S>,
         q: &CallbackQuery,
         lookup: &dyn SceneLookup,
+        group_mode: bool,
     ) where
         D: UiStore,
         S: dialogue::Storage<D>,
@@ -322,6 +532,15 @@ impl<M: Store> Viewport<M> {
         let mid = msg.id().0;
         let chat = msg.chat().id;
         let data = q.data.as_deref().unwrap_or("");
+        let meta = self.load_meta(chat, mid).await.ok().flatten();
+
+        // Someone else's menu: leave the presser's session alone
+        if group_mode
+            && let Some(owner) = meta.as_ref().and_then(|m| m.owner_id)
+            && owner != q.from.id.0 as i64
+        {
+            return;
+        }
 
         if let Ok(mut s) = d.get_or_default().await {
             let is_prompt_click = s.ui_get_input_prompt_message_id() == Some(mid);
@@ -332,7 +551,7 @@ impl<M: Store> Viewport<M> {
                 s.ui_set_reply_to_last_once(true);
             }
 
-            if let Ok(Some(meta)) = self.load_meta(chat, mid).await {
+            if let Some(meta) = meta {
                 s.ui_set_scene_for_message(
                     mid,
                     serde_json::to_string(&meta.scene_id).unwrap_or("null".into()),
@@ -354,6 +573,250 @@ impl<M: Store> Viewport<M> {
         }
     }
 
+    /// Swap oversized callback data for tokens, then sign it.
+    async fn seal_callbacks<'v>(&self, view: &'v View) -> anyhow::Result<Cow<'v, View>> {
+        let Some(ReplyMarkup::InlineKeyboard(mk)) = &view.markup else {
+            return Ok(Cow::Borrowed(view));
+        };
+        let has_callbacks = mk
+            .inline_keyboard
+            .iter()
+            .flatten()
+            .any(|b| matches!(b.kind, InlineKeyboardButtonKind::CallbackData(_)));
+        if !has_callbacks || (!self.payload_tokens && self.signer.is_none()) {
+            return Ok(Cow::Borrowed(view));
+        }
+
+        let max = match self.signer {
+            Some(_) => CALLBACK_DATA_MAX - CallbackSigner::OVERHEAD,
+            None => CALLBACK_DATA_MAX,
+        };
+
+        let mut mk = mk.clone();
+        for button in mk.inline_keyboard.iter_mut().flatten() {
+            let InlineKeyboardButtonKind::CallbackData(data) = &mut button.kind else {
+                continue;
+            };
+
+            // The signature can push data that fit past the limit
+            if data.len() > max || !data.is_ascii() {
+                let token = self.token(callback::PAYLOAD_TOKEN, data);
+                match self.meta.save_payload(&token, data, SNAP_TTL_SECS).await {
+                    Ok(()) => *data = token,
+                    // Keep the menu; only this button goes unsigned
+                    Err(e) if self.signer.is_some() => {
+                        tracing::warn!(
+                            error=?e,
+                            len = data.len(),
+                            "callback data too long to sign, sent unsigned"
+                        );
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if let Some(signer) = &self.signer
+                && CallbackSigner::needs_signature(data)
+            {
+                *data = signer.sign(data);
+            }
+        }
+
+        let mut view = view.clone();
+        view.markup = Some(mk.into());
+
+        Ok(Cow::Owned(view))
+    }
+
+    fn token(&self, prefix: &str, payload: &str) -> String {
+        // Content-addressed: re-renders reuse the token
+        let hash = match &self.signer {
+            Some(signer) => signer.token_hash(payload),
+            None => blake3_hex(payload.as_bytes()),
+        };
+        format!("{prefix}{}", &hash[..24])
+    }
+
+    /// Payload stored behind a `ui:p:` token,
+    /// `None` once it expired.
+    #[instrument(name = "viewport.load_payload", skip(self))]
+    pub async fn load_payload(&self, token: &str) -> anyhow::Result<Option<String>> {
+        self.meta.load_payload(token).await
+    }
+
+    /// Take a token from bucket `key`, in process memory when
+    /// the store keeps no buckets. Store errors let the action through.
+    pub async fn take_token(&self, key: &str, limit: RateLimit) -> bool {
+        match self.meta.take_token(key, limit).await {
+            Ok(Some(ok)) => ok,
+            Ok(None) => !matches!(self.local.take_token(key, limit).await, Ok(Some(false))),
+            Err(e) => {
+                tracing::warn!(error=?e, key, "rate limiter unavailable");
+                true
+            }
+        }
+    }
+
+    /// Chat blocked or removed the bot; shared by
+    /// every dialogue in it, unlike session state.
+    /// `meta` is read once per chat, later checks use
+    /// the last known flag, refreshed when the chat writes.
+    pub async fn is_unreachable(&self, chat: ChatId) -> bool {
+        if let Some(reachable) = self.local.cached_reachable(chat) {
+            return !reachable;
+        }
+
+        let v = match self.meta.load_unreachable(chat).await {
+            Ok(v) => v.unwrap_or(false),
+            Err(e) => {
+                tracing::warn!(error=?e, chat=%chat.0, "unreachable flag unavailable");
+                return false;
+            }
+        };
+        self.local.cache_reachable(chat, !v);
+
+        v
+    }
+
+    pub async fn set_unreachable(&self, chat: ChatId, v: bool) {
+        if self.is_unreachable(chat).await == v {
+            return;
+        }
+
+        self.local.cache_reachable(chat, !v);
+        if let Err(e) = self.meta.save_unreachable(chat, v).await {
+            tracing::warn!(error=?e, chat=%chat.0, "unreachable flag not saved");
+        }
+    }
+
+    #[instrument(name = "viewport.schedule_event", skip(self, payload))]
+    pub async fn schedule_event(
+        &self,
+        chat: ChatId,
+        user_id: i64,
+        mid: i32,
+        scene_id: &str,
+        payload: String,
+        delay_secs: u64,
+    ) -> anyhow::Result<()> {
+        self.meta
+            .schedule(ScheduledEvent {
+                chat_id: chat.0,
+                user_id: Some(user_id),
+                message_id: mid,
+                scene_id: scene_id.to_string(),
+                payload,
+                due_at: unix_now() + delay_secs as i64,
+            })
+            .await
+    }
+
+    /// Article for an inline query; `spec` is kept until
+    /// [`Self::bind_inline_result`] sees the result chosen.
+    #[instrument(name = "viewport.inline_article", skip(self, view, description, spec))]
+    pub async fn inline_article(
+        &self,
+        view: &View,
+        title: String,
+        description: Option<String>,
+        spec: MetaSpec,
+    ) -> anyhow::Result<InlineQueryResult> {
+        let json = serde_json::to_string(&message_meta(spec))?;
+        let id = self.token(callback::INLINE_RESULT, &json);
+        self.meta
+            .save_payload(&id, &json, INLINE_RESULT_TTL_SECS)
+            .await?;
+
+        let view = self.seal_callbacks(view).await?;
+        let content = InputMessageContentText {
+            message_text: view.text.clone(),
+            parse_mode: view.parse_mode,
+            entities: None,
+            link_preview_options: link_preview(&view),
+        };
+
+        let mut article =
+            InlineQueryResultArticle::new(id, title, InputMessageContent::Text(content));
+        article.description = description;
+        article.reply_markup = inline_markup(&view);
+
+        Ok(article.into())
+    }
+
+    /// Attach the meta of a chosen result to its inline message;
+    /// `false` for results not built by [`Self::inline_article`].
+    #[instrument(name = "viewport.bind_inline_result", skip(self))]
+    pub async fn bind_inline_result(
+        &self,
+        result_id: &str,
+        inline_id: &str,
+    ) -> anyhow::Result<bool> {
+        if !result_id.starts_with(callback::INLINE_RESULT) {
+            return Ok(false);
+        }
+        let Some(json) = self.meta.load_payload(result_id).await? else {
+            return Ok(false);
+        };
+
+        let mut meta: MessageMeta = serde_json::from_str(&json)?;
+        meta.created_at = unix_now();
+        self.meta.save_inline(inline_id, meta).await?;
+
+        Ok(true)
+    }
+
+    #[instrument(name = "viewport.load_inline_meta", skip(self))]
+    pub async fn load_inline_meta(&self, inline_id: &str) -> anyhow::Result<Option<MessageMeta>> {
+        self.meta.load_inline(inline_id).await
+    }
+
+    /// Edit a message sent in inline mode; media
+    /// is not supported there, only text is applied.
+    #[instrument(name = "viewport.apply_inline_view", skip(self, bot, view, meta))]
+    pub async fn apply_inline_view<R>(
+        &self,
+        bot: &R,
+        inline_id: &str,
+        view: &View,
+        meta: Option<MetaSpec>,
+    ) -> anyhow::Result<()>
+    where
+        R: UiRequester,
+        <R as Requester>::EditMessageTextInline: Send,
+    {
+        let view = self.seal_callbacks(view).await?;
+
+        let mut req = bot.edit_message_text_inline(inline_id, view.text.clone());
+        if let Some(pm) = view.parse_mode {
+            req = req.parse_mode(pm);
+        }
+        if let Some(disable) = view.disable_web_page_preview {
+            req = req.disable_web_page_preview(disable);
+        }
+        if let Some(mk) = inline_markup(&view) {
+            req = req.reply_markup(mk);
+        }
+
+        match req.await {
+            Ok(_) | Err(teloxide::RequestError::Api(teloxide::ApiError::MessageNotModified)) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        if let Some(spec) = meta {
+            self.meta.save_inline(inline_id, message_meta(spec)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Claim events whose delay has passed; pass
+    /// each to [`crate::router::Router::deliver`].
+    #[instrument(name = "viewport.take_due_events", skip(self))]
+    pub async fn take_due_events(&self, limit: usize) -> anyhow::Result<Vec<ScheduledEvent>> {
+        self.meta.take_due(unix_now(), limit).await
+    }
+
     // Test/public utility to store
     // meta without sending messages.
     pub async fn save_meta_public(
@@ -362,35 +825,291 @@ impl<M: Store> Viewport<M> {
         mid: i32,
         spec: MetaSpec,
     ) -> anyhow::Result<()> {
-        let checksum = spec.state_json.as_ref().map(|s| blake3_hex(s.as_bytes()));
-        let created_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs() as i64;
-
-        let meta = MessageMeta {
-            scene_id: spec.scene_id.to_string(),
-            scene_version: spec.scene_version,
-            state_json: spec.state_json,
-            state_ref: spec.state_ref,
-            state_checksum: checksum,
-            created_at,
-            ttl_secs: spec.ttl_secs,
-        };
-
-        self.meta.save(chat, mid, meta).await
+        self.meta.save(chat, mid, message_meta(spec)).await
     }
 }
 
 #[cfg(feature = "redis")]
 impl Viewport<redis::RedisStore> {
     pub async fn redis(url: &str) -> anyhow::Result<Self> {
-        Ok(Self {
-            meta: redis::RedisStore::new(url).await?,
-        })
+        Ok(Self::new(redis::RedisStore::new(url).await?))
+    }
+}
+
+fn media_key(media: &Media) -> String {
+    let source = match &media.source {
+        MediaSource::FileId(id) => format!("id:{id}"),
+        MediaSource::Url(url) => format!("url:{url}"),
+        MediaSource::Bytes { data, .. } => format!("b3:{}", blake3_hex(data)),
+    };
+
+    format!("{:?}:{source}", media.kind)
+}
+
+fn input_file(source: &MediaSource) -> InputFile {
+    match source {
+        MediaSource::FileId(id) => InputFile::file_id(FileId(id.clone())),
+        MediaSource::Url(url) => InputFile::url(url.clone()),
+        MediaSource::Bytes { file_name, data } => {
+            InputFile::memory(data.clone()).file_name(file_name.clone())
+        }
+    }
+}
+
+fn inline_markup(view: &View) -> Option<InlineKeyboardMarkup> {
+    match &view.markup {
+        Some(ReplyMarkup::InlineKeyboard(mk)) => Some(mk.clone()),
+        _ => None,
+    }
+}
+
+fn caption(view: &View) -> Option<String> {
+    (!view.text.is_empty()).then(|| view.text.clone())
+}
+
+async fn send_media<R>(
+    bot: &R,
+    chat: ChatId,
+    view: &View,
+    media: &Media,
+    markup: Option<ReplyMarkup>,
+    reply_to: Option<MessageId>,
+) -> Result<Message, teloxide::RequestError>
+where
+    R: UiRequester,
+    <R as Requester>::SendPhoto: Send,
+    <R as Requester>::SendAnimation: Send,
+    <R as Requester>::SendDocument: Send,
+{
+    let file = input_file(&media.source);
+
+    // Same setters on three distinct request types
+    macro_rules! send {
+        ($req:expr) => {{
+            let mut req = $req;
+            if let Some(c) = caption(view) {
+                req = req.caption(c);
+            }
+
+            if let Some(pm) = view.parse_mode {
+                req = req.parse_mode(pm);
+            }
+
+            if let Some(mk) = markup {
+                req = req.reply_markup(mk);
+            }
+
+            if let Some(to_mid) = reply_to {
+                req = req.reply_to(to_mid);
+            }
+
+            req.await
+        }};
+    }
+
+    match media.kind {
+        MediaKind::Photo => send!(bot.send_photo(chat, file)),
+        MediaKind::Animation => send!(bot.send_animation(chat, file)),
+        MediaKind::Document => send!(bot.send_document(chat, file)),
     }
 }
 
+/// Edit only the caption when the media is
+/// unchanged, otherwise swap the media.
+async fn edit_media<R>(
+    bot: &R,
+    chat: ChatId,
+    mid: MessageId,
+    view: &View,
+    media: &Media,
+    last_key: Option<&str>,
+) -> Result<(), teloxide::RequestError>
+where
+    R: UiRequester,
+    <R as Requester>::EditMessageMedia: Send,
+    <R as Requester>::EditMessageCaption: Send,
+{
+    let res = if last_key == Some(media_key(media).as_str()) {
+        let mut req = bot.edit_message_caption(chat, mid);
+        if let Some(c) = caption(view) {
+            req = req.caption(c);
+        }
+
+        if let Some(pm) = view.parse_mode {
+            req = req.parse_mode(pm);
+        }
+
+        if let Some(mk) = inline_markup(view) {
+            req = req.reply_markup(mk);
+        }
+
+        req.await.map(|_| ())
+    } else {
+        let file = input_file(&media.source);
+        let input = match media.kind {
+            MediaKind::Photo => {
+                let mut m = InputMediaPhoto::new(file);
+                m.caption = caption(view);
+                m.parse_mode = view.parse_mode;
+                InputMedia::Photo(m)
+            }
+            MediaKind::Animation => {
+                let mut m = InputMediaAnimation::new(file);
+                m.caption = caption(view);
+                m.parse_mode = view.parse_mode;
+                InputMedia::Animation(m)
+            }
+            MediaKind::Document => {
+                let mut m = InputMediaDocument::new(file);
+                m.caption = caption(view);
+                m.parse_mode = view.parse_mode;
+                InputMedia::Document(m)
+            }
+        };
+
+        let mut req = bot.edit_message_media(chat, mid, input);
+        if let Some(mk) = inline_markup(view) {
+            req = req.reply_markup(mk);
+        }
+
+        req.await.map(|_| ())
+    };
+
+    match res {
+        Err(teloxide::RequestError::Api(teloxide::ApiError::MessageNotModified)) => Ok(()),
+        other => other,
+    }
+}
+
+/// Send `view` as a new message, deleting `previous`.
+async fn send_view<R, D, S>(
+    bot: &R,
+    chat: ChatId,
+    d: &Dialogue<D, S>,
+    previous: Option<MessageId>,
+    view: &View,
+) -> Result<MessageId, teloxide::RequestError>
+where
+    R: UiRequester,
+    <R as Requester>::SendMessage: Send,
+    <R as Requester>::DeleteMessage: Send,
+    <R as Requester>::SendPhoto: Send,
+    <R as Requester>::SendAnimation: Send,
+    <R as Requester>::SendDocument: Send,
+    D: UiStore + Send + Sync,
+    S: UiDialogueStorage<D>,
+    <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+{
+    match &view.media {
+        Some(media) => reply_media(bot, chat, d, previous, view, media).await,
+        None => {
+            message::compact_reply(
+                bot,
+                chat,
+                d,
+                previous,
+                view.text.clone(),
+                message::ReplyOptions {
+                    markup: view.markup.clone(),
+                    parse_mode: view.parse_mode,
+                    disable_web_page_preview: view.disable_web_page_preview,
+                },
+                None,
+            )
+            .await
+        }
+    }
+}
+
+/// Media counterpart of [`message::compact_reply`].
+async fn reply_media<R, D, S>(
+    bot: &R,
+    chat: ChatId,
+    d: &Dialogue<D, S>,
+    previous: Option<MessageId>,
+    view: &View,
+    media: &Media,
+) -> Result<MessageId, teloxide::RequestError>
+where
+    R: UiRequester,
+    <R as Requester>::SendPhoto: Send,
+    <R as Requester>::SendAnimation: Send,
+    <R as Requester>::SendDocument: Send,
+    D: UiStore + Send + Sync,
+    S: UiDialogueStorage<D>,
+    <S as dialogue::Storage<D>>::Error: std::fmt::Debug + Send,
+{
+    if let Some(prev) = previous
+        && let Err(e) = bot.delete_message(chat, prev).await
+    {
+        tracing::debug!(
+            error=?e,
+            chat=%chat.0,
+            prev=%prev.0,
+            "delete previous message failed",
+        );
+    }
+
+    let msg = send_media(bot, chat, view, media, view.markup.clone(), None).await?;
+    let new_id = msg.id;
+
+    if let Ok(mut s) = d.get_or_default().await {
+        let cur_scene_json = s.ui_get_current_scene_json();
+
+        s.ui_set_scene_for_message(new_id.0, cur_scene_json);
+        s.ui_set_last_action_message_id(Some(new_id.0));
+
+        if let Err(e) = d.update(s).await {
+            tracing::error!(
+                error=?e,
+                chat=%chat.0,
+                mid=%new_id.0,
+                "dialogue update failed (reply_media)",
+            );
+        }
+    }
+
+    Ok(new_id)
+}
+
+fn message_meta(spec: MetaSpec) -> MessageMeta {
+    MessageMeta {
+        scene_id: spec.scene_id.to_string(),
+        scene_version: spec.scene_version,
+        state_checksum: spec.state_json.as_ref().map(|s| blake3_hex(s.as_bytes())),
+        state_json: spec.state_json,
+        state_ref: spec.state_ref,
+        created_at: unix_now(),
+        ttl_secs: spec.ttl_secs,
+        owner_id: spec.owner_id,
+        media: None,
+    }
+}
+
+fn link_preview(view: &View) -> Option<LinkPreviewOptions> {
+    (view.disable_web_page_preview == Some(true)).then_some(LinkPreviewOptions {
+        is_disabled: true,
+        url: None,
+        prefer_small_media: false,
+        prefer_large_media: false,
+        show_above_text: false,
+    })
+}
+
+pub(crate) fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+pub(crate) fn unix_now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
 pub fn blake3_hex(input: &[u8]) -> String {
     let h = blake3::hash(input);
     hex::encode(h.as_bytes())
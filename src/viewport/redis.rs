@@ -1,4 +1,4 @@
-use crate::viewport::store::Store;
+use crate::viewport::store::{RateLimit, Store};
 use redis::{AsyncCommands, aio::ConnectionManager};
 use teloxide::types::ChatId;
 
@@ -9,6 +9,26 @@ use {
     rand::RngCore,
 };
 
+// Token bucket in one round trip; KEYS[1] bucket,
+// ARGV burst, refill window (ms), now (ms).
+const TAKE_TOKEN_LUA: &str = r#"
+local burst = tonumber(ARGV[1])
+local per = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local b = redis.call('HMGET', KEYS[1], 't', 'ts')
+local tokens = tonumber(b[1]) or burst
+local ts = tonumber(b[2]) or now
+tokens = math.min(burst, tokens + (now - ts) * burst / per)
+local ok = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    ok = 1
+end
+redis.call('HSET', KEYS[1], 't', tostring(tokens), 'ts', tostring(now))
+redis.call('PEXPIRE', KEYS[1], per)
+return ok
+"#;
+
 #[derive(Clone)]
 pub struct RedisStore {
     redis: ConnectionManager,
@@ -61,8 +81,43 @@ impl RedisStore {
         format!("{}:{}:{}", self.namespace, chat.0, mid)
     }
 
+    fn timers_key(&self) -> String {
+        format!("{}:timers", self.namespace)
+    }
+
+    fn payload_key(&self, token: &str) -> String {
+        format!("{}:cb:{}", self.namespace, token)
+    }
+
+    fn inline_key(&self, inline_id: &str) -> String {
+        format!("{}:inline:{}", self.namespace, inline_id)
+    }
+
+    fn unreachable_key(&self, chat: ChatId) -> String {
+        format!("{}:unreachable:{}", self.namespace, chat.0)
+    }
+
+    fn bucket_key(&self, key: &str) -> String {
+        format!("{}:rl:{}", self.namespace, key)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn aad(&self, chat: ChatId, mid: i32) -> String {
+        format!("ns={};chat={};mid={}", self.namespace, chat.0, mid)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn inline_aad(&self, inline_id: &str) -> String {
+        format!("ns={};inline={}", self.namespace, inline_id)
+    }
+
+    #[cfg(feature = "encryption")]
+    fn payload_aad(&self, token: &str) -> String {
+        format!("ns={};payload={}", self.namespace, token)
+    }
+
     #[cfg(feature = "encryption")]
-    fn encrypt(&self, chat: ChatId, mid: i32, plaintext_json: &str) -> anyhow::Result<String> {
+    fn encrypt(&self, aad: &str, plaintext_json: &str) -> anyhow::Result<String> {
         let Some(key_bytes) = self.enc_key.as_ref() else {
             return Ok(plaintext_json.to_string());
         };
@@ -72,7 +127,6 @@ impl RedisStore {
         rand::rngs::OsRng.fill_bytes(&mut nonce_bytes);
 
         let nonce = Nonce::from_slice(&nonce_bytes);
-        let aad = format!("ns={};chat={};mid={}", self.namespace, chat.0, mid);
         let ct = cipher
             .encrypt(
                 nonce,
@@ -92,7 +146,7 @@ impl RedisStore {
     }
 
     #[cfg(feature = "encryption")]
-    fn try_decrypt(&self, chat: ChatId, mid: i32, val: &str) -> anyhow::Result<String> {
+    fn try_decrypt(&self, aad: &str, val: &str) -> anyhow::Result<String> {
         // Detect encrypted wrapper
         let Ok(v) = serde_json::from_str::<serde_json::Value>(val) else {
             return Ok(val.to_string());
@@ -119,7 +173,6 @@ impl RedisStore {
         }
 
         let cipher = ChaCha20Poly1305::new(Key::from_slice(key_bytes));
-        let aad = format!("ns={};chat={};mid={}", self.namespace, chat.0, mid);
         let pt = cipher
             .decrypt(
                 Nonce::from_slice(&nonce_vec),
@@ -146,7 +199,7 @@ impl Store for RedisStore {
         let json = serde_json::to_string(&meta)?;
 
         #[cfg(feature = "encryption")]
-        let val = self.encrypt(chat, mid, &json)?;
+        let val = self.encrypt(&self.aad(chat, mid), &json)?;
         #[cfg(not(feature = "encryption"))]
         let val = json;
 
@@ -169,7 +222,7 @@ impl Store for RedisStore {
 
         if let Some(v) = val {
             #[cfg(feature = "encryption")]
-            let raw = self.try_decrypt(chat, mid, &v)?;
+            let raw = self.try_decrypt(&self.aad(chat, mid), &v)?;
             #[cfg(not(feature = "encryption"))]
             let raw = v;
 
@@ -178,4 +231,151 @@ impl Store for RedisStore {
             Ok(None)
         }
     }
+
+    async fn schedule(&self, ev: crate::viewport::ScheduledEvent) -> anyhow::Result<()> {
+        let json = serde_json::to_string(&ev)?;
+        let mut conn = self.redis.clone();
+
+        let _: () = conn.zadd(self.timers_key(), json, ev.due_at).await?;
+
+        Ok(())
+    }
+
+    async fn take_due(
+        &self,
+        now: i64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<crate::viewport::ScheduledEvent>> {
+        let key = self.timers_key();
+        let mut conn = self.redis.clone();
+
+        let due: Vec<String> = conn
+            .zrangebyscore_limit(&key, "-inf", now, 0, limit as isize)
+            .await?;
+
+        let mut out = Vec::with_capacity(due.len());
+        for member in due {
+            // ZREM claims the event so concurrent
+            // pollers never deliver it twice.
+            let removed: i64 = conn.zrem(&key, &member).await?;
+            if removed == 0 {
+                continue;
+            }
+
+            match serde_json::from_str(&member) {
+                Ok(ev) => out.push(ev),
+                Err(e) => tracing::warn!(error=?e, "scheduled event decode failed"),
+            }
+        }
+
+        Ok(out)
+    }
+
+    async fn save_payload(&self, token: &str, payload: &str, ttl_secs: u32) -> anyhow::Result<()> {
+        // Payloads carry callback data and inline
+        // result snapshots; encrypt them like meta.
+        #[cfg(feature = "encryption")]
+        let val = self.encrypt(&self.payload_aad(token), payload)?;
+        #[cfg(not(feature = "encryption"))]
+        let val = payload;
+
+        let mut conn = self.redis.clone();
+
+        let _: () = conn
+            .set_ex(self.payload_key(token), val, ttl_secs as u64)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load_payload(&self, token: &str) -> anyhow::Result<Option<String>> {
+        let mut conn = self.redis.clone();
+
+        let val: Option<String> = conn.get(self.payload_key(token)).await?;
+
+        #[cfg(feature = "encryption")]
+        let val = match val {
+            Some(v) => Some(self.try_decrypt(&self.payload_aad(token), &v)?),
+            None => None,
+        };
+
+        Ok(val)
+    }
+
+    async fn save_inline(
+        &self,
+        inline_id: &str,
+        meta: crate::viewport::MessageMeta,
+    ) -> anyhow::Result<()> {
+        let json = serde_json::to_string(&meta)?;
+
+        #[cfg(feature = "encryption")]
+        let val = self.encrypt(&self.inline_aad(inline_id), &json)?;
+        #[cfg(not(feature = "encryption"))]
+        let val = json;
+
+        let mut conn = self.redis.clone();
+
+        let _: () = conn
+            .set_ex(self.inline_key(inline_id), val, meta.ttl_secs as u64)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load_inline(
+        &self,
+        inline_id: &str,
+    ) -> anyhow::Result<Option<crate::viewport::MessageMeta>> {
+        let mut conn = self.redis.clone();
+
+        let val: Option<String> = conn.get(self.inline_key(inline_id)).await?;
+
+        if let Some(v) = val {
+            #[cfg(feature = "encryption")]
+            let raw = self.try_decrypt(&self.inline_aad(inline_id), &v)?;
+            #[cfg(not(feature = "encryption"))]
+            let raw = v;
+
+            Ok(serde_json::from_str(&raw).ok())
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn take_token(&self, key: &str, limit: RateLimit) -> anyhow::Result<Option<bool>> {
+        let mut conn = self.redis.clone();
+
+        let ok: i64 = redis::cmd("EVAL")
+            .arg(TAKE_TOKEN_LUA)
+            .arg(1)
+            .arg(self.bucket_key(key))
+            .arg(limit.burst)
+            .arg(limit.per.as_millis().max(1) as u64)
+            .arg(crate::viewport::unix_now_ms())
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(Some(ok == 1))
+    }
+
+    async fn load_unreachable(&self, chat: ChatId) -> anyhow::Result<Option<bool>> {
+        let mut conn = self.redis.clone();
+
+        let exists: bool = conn.exists(self.unreachable_key(chat)).await?;
+
+        Ok(Some(exists))
+    }
+
+    async fn save_unreachable(&self, chat: ChatId, v: bool) -> anyhow::Result<()> {
+        let mut conn = self.redis.clone();
+
+        if v {
+            let _: () = conn.set(self.unreachable_key(chat), 1).await?;
+        } else {
+            let _: () = conn.del(self.unreachable_key(chat)).await?;
+        }
+
+        Ok(())
+    }
 }
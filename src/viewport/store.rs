@@ -1,10 +1,92 @@
-use crate::viewport::MessageMeta;
+use crate::viewport::{MessageMeta, ScheduledEvent};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use teloxide::types::ChatId;
 
+// Expired entries are swept on writes at most this often
+const PURGE_EVERY_SECS: i64 = 60;
+// Due timers nobody claimed point at long-gone messages by then
+const STALE_TIMER_SECS: i64 = crate::viewport::SNAP_TTL_SECS as i64;
+
+/// Token bucket: up to `burst` actions, refilled
+/// evenly so that `burst` more fit into every `per`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, per: Duration) -> Self {
+        Self { burst, per }
+    }
+
+    /// One action per `cooldown`.
+    pub fn cooldown(cooldown: Duration) -> Self {
+        Self::new(1, cooldown)
+    }
+
+    fn per_ms(&self) -> f64 {
+        (self.per.as_millis() as f64).max(1.0)
+    }
+}
+
 #[async_trait::async_trait]
 pub trait Store: Send + Sync + 'static {
     async fn save(&self, chat: ChatId, mid: i32, meta: MessageMeta) -> anyhow::Result<()>;
     async fn load(&self, chat: ChatId, mid: i32) -> anyhow::Result<Option<MessageMeta>>;
+
+    /// Persist a delayed scene event.
+    async fn schedule(&self, _ev: ScheduledEvent) -> anyhow::Result<()> {
+        anyhow::bail!("scheduled events are not supported by this store")
+    }
+
+    /// Remove and return up to `limit`
+    /// events due at or before `now`.
+    async fn take_due(&self, _now: i64, _limit: usize) -> anyhow::Result<Vec<ScheduledEvent>> {
+        Ok(vec![])
+    }
+
+    /// Keep an oversized callback payload behind `token`.
+    async fn save_payload(
+        &self,
+        _token: &str,
+        _payload: &str,
+        _ttl_secs: u32,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!("callback payloads are not supported by this store")
+    }
+
+    async fn load_payload(&self, _token: &str) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Meta of a message sent in inline mode.
+    async fn save_inline(&self, _inline_id: &str, _meta: MessageMeta) -> anyhow::Result<()> {
+        anyhow::bail!("inline messages are not supported by this store")
+    }
+
+    async fn load_inline(&self, _inline_id: &str) -> anyhow::Result<Option<MessageMeta>> {
+        Ok(None)
+    }
+
+    /// Take a token from bucket `key`; `false` when it's empty.
+    /// `None` when this store keeps no buckets and the
+    /// viewport limits with process-local ones instead.
+    async fn take_token(&self, _key: &str, _limit: RateLimit) -> anyhow::Result<Option<bool>> {
+        Ok(None)
+    }
+
+    /// Chat blocked or removed the bot; `None` when this store
+    /// doesn't keep the flag and the viewport tracks it itself.
+    async fn load_unreachable(&self, _chat: ChatId) -> anyhow::Result<Option<bool>> {
+        Ok(None)
+    }
+
+    async fn save_unreachable(&self, _chat: ChatId, _v: bool) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Clone, Copy, Default)]
@@ -20,3 +102,210 @@ impl Store for NoopStore {
         Ok(None)
     }
 }
+
+/// Process-local store; state is lost on restart.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    inner: Arc<Mutex<MemoryInner>>,
+}
+
+impl MemoryStore {
+    pub(crate) fn cached_reachable(&self, chat: ChatId) -> Option<bool> {
+        let g = self.inner.lock().ok()?;
+        g.reachable.get(&chat.0).copied()
+    }
+
+    pub(crate) fn cache_reachable(&self, chat: ChatId, v: bool) {
+        if let Ok(mut g) = self.inner.lock() {
+            g.reachable.insert(chat.0, v);
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryInner {
+    meta: HashMap<(i64, i32), MessageMeta>,
+    timers: Vec<ScheduledEvent>,
+    payloads: HashMap<String, (String, i64)>,
+    inline: HashMap<String, MessageMeta>,
+    // key -> (tokens, updated at ms, full again at ms)
+    buckets: HashMap<String, (f64, i64, i64)>,
+    unreachable: HashSet<i64>,
+    // Last known flag per chat, see `Viewport::is_unreachable`
+    reachable: HashMap<i64, bool>,
+    purged_at: i64,
+}
+
+impl MemoryInner {
+    fn purge(&mut self) {
+        let now_ms = crate::viewport::unix_now_ms();
+        let now = now_ms / 1000;
+        if now - self.purged_at < PURGE_EVERY_SECS {
+            return;
+        }
+        self.purged_at = now;
+
+        self.meta
+            .retain(|_, m| m.created_at + m.ttl_secs as i64 > now);
+        self.inline
+            .retain(|_, m| m.created_at + m.ttl_secs as i64 > now);
+        self.payloads.retain(|_, (_, expires_at)| *expires_at > now);
+        self.timers.retain(|t| t.due_at + STALE_TIMER_SECS > now);
+        // A full bucket is the same as none
+        self.buckets.retain(|_, (_, _, full_at)| *full_at > now_ms);
+    }
+}
+
+#[async_trait::async_trait]
+impl Store for MemoryStore {
+    async fn save(&self, chat: ChatId, mid: i32, meta: MessageMeta) -> anyhow::Result<()> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        g.purge();
+        g.meta.insert((chat.0, mid), meta);
+
+        Ok(())
+    }
+
+    async fn load(&self, chat: ChatId, mid: i32) -> anyhow::Result<Option<MessageMeta>> {
+        let g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        let now = crate::viewport::unix_now();
+
+        Ok(g.meta
+            .get(&(chat.0, mid))
+            .filter(|m| m.created_at + m.ttl_secs as i64 > now)
+            .cloned())
+    }
+
+    async fn schedule(&self, ev: ScheduledEvent) -> anyhow::Result<()> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        g.purge();
+        g.timers.push(ev);
+
+        Ok(())
+    }
+
+    async fn take_due(&self, now: i64, limit: usize) -> anyhow::Result<Vec<ScheduledEvent>> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        g.timers.sort_by_key(|t| t.due_at);
+
+        let n = g
+            .timers
+            .iter()
+            .take(limit)
+            .take_while(|t| t.due_at <= now)
+            .count();
+
+        Ok(g.timers.drain(..n).collect())
+    }
+
+    async fn save_payload(&self, token: &str, payload: &str, ttl_secs: u32) -> anyhow::Result<()> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        g.purge();
+        let expires_at = crate::viewport::unix_now() + ttl_secs as i64;
+        g.payloads
+            .insert(token.to_string(), (payload.to_string(), expires_at));
+
+        Ok(())
+    }
+
+    async fn load_payload(&self, token: &str) -> anyhow::Result<Option<String>> {
+        let g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        let now = crate::viewport::unix_now();
+
+        Ok(g.payloads
+            .get(token)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(p, _)| p.clone()))
+    }
+
+    async fn save_inline(&self, inline_id: &str, meta: MessageMeta) -> anyhow::Result<()> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        g.purge();
+        g.inline.insert(inline_id.to_string(), meta);
+
+        Ok(())
+    }
+
+    async fn load_inline(&self, inline_id: &str) -> anyhow::Result<Option<MessageMeta>> {
+        let g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        let now = crate::viewport::unix_now();
+
+        Ok(g.inline
+            .get(inline_id)
+            .filter(|m| m.created_at + m.ttl_secs as i64 > now)
+            .cloned())
+    }
+
+    async fn take_token(&self, key: &str, limit: RateLimit) -> anyhow::Result<Option<bool>> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        g.purge();
+        let now = crate::viewport::unix_now_ms();
+        let burst = limit.burst as f64;
+        let rate = burst / limit.per_ms();
+
+        let (tokens, at, full_at) = g
+            .buckets
+            .entry(key.to_string())
+            .or_insert((burst, now, now));
+        *tokens = (*tokens + (now - *at) as f64 * rate).min(burst);
+        *at = now;
+
+        let ok = *tokens >= 1.0;
+        if ok {
+            *tokens -= 1.0;
+        }
+        *full_at = now + ((burst - *tokens) / rate).ceil() as i64;
+
+        Ok(Some(ok))
+    }
+
+    async fn load_unreachable(&self, chat: ChatId) -> anyhow::Result<Option<bool>> {
+        let g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+
+        Ok(Some(g.unreachable.contains(&chat.0)))
+    }
+
+    async fn save_unreachable(&self, chat: ChatId, v: bool) -> anyhow::Result<()> {
+        let mut g = self
+            .inner
+            .lock()
+            .map_err(|_| anyhow::anyhow!("store poisoned"))?;
+        if v {
+            g.unreachable.insert(chat.0);
+        } else {
+            g.unreachable.remove(&chat.0);
+        }
+
+        Ok(())
+    }
+}
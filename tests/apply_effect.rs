@@ -98,6 +98,10 @@ impl AppCtx for TestAppCtx {
     fn user_id(&self) -> i64 {
         self.chat.0
     }
+
+    fn bot_username(&self) -> Option<&str> {
+        Some("botkit_bot")
+    }
 }
 
 struct TestScene;
@@ -145,10 +149,11 @@ impl Scene for TestScene {
             markup: None,
             parse_mode: None,
             disable_web_page_preview: None,
+            media: None,
         }
     }
 
-    fn update(&self, _c: &Ctx, s: &State, _e: Event) -> Effect<State> {
+    fn update(&self, _c: &Ctx, s: &State, _e: Event) -> Effect<State, Event> {
         Effect::Stay(s.clone(), RenderPolicy::EditOrReply)
     }
 
@@ -201,6 +206,7 @@ async fn apply_effect_stay_edit_or_reply_sets_last_message() {
 
     let sctx = Ctx {
         user_id: ctx.user_id(),
+        ..Default::default()
     };
     let eff = Effect::Stay(State::Root, RenderPolicy::EditOrReply);
 
@@ -240,10 +246,11 @@ impl Scene for TestScene2 {
             markup: None,
             parse_mode: None,
             disable_web_page_preview: None,
+            media: None,
         }
     }
 
-    fn update(&self, _c: &Ctx, s: &State, _e: Event) -> Effect<State> {
+    fn update(&self, _c: &Ctx, s: &State, _e: Event) -> Effect<State, Event> {
         Effect::Stay(s.clone(), RenderPolicy::EditOrReply)
     }
 
@@ -292,6 +299,7 @@ async fn apply_effect_stay_with_ui_effects_clears_prompt() {
 
     let sctx = Ctx {
         user_id: ctx.user_id(),
+        ..Default::default()
     };
     let eff = Effect::StayWithEffect(
         State::Root,
@@ -342,9 +350,10 @@ async fn apply_effect_edit_only_does_not_create_new_message() {
     // First create a message
     let view = View {
         text: "menu".into(),
-        markup: Some(teloxide::types::InlineKeyboardMarkup::new(vec![vec![]])),
+        markup: Some(teloxide::types::InlineKeyboardMarkup::new(vec![vec![]]).into()),
         parse_mode: None,
         disable_web_page_preview: None,
+        media: None,
     };
 
     vp.apply_view(
@@ -366,6 +375,7 @@ async fn apply_effect_edit_only_does_not_create_new_message() {
 
     let sctx = Ctx {
         user_id: ctx.user_id(),
+        ..Default::default()
     };
     let eff = Effect::Stay(State::Root, RenderPolicy::EditOnly);
     apply_effect(&routes, &TestScene, &ctx, &vp, &d, &sctx, eff)
@@ -412,6 +422,7 @@ async fn apply_effect_noop_does_not_change_last_message() {
 
     let sctx = Ctx {
         user_id: ctx.user_id(),
+        ..Default::default()
     };
     let before = d
         .get_or_default()
@@ -479,9 +490,11 @@ async fn apply_effect_switch_scene_sets_active_scene_id() {
 
     let sctx = Ctx {
         user_id: ctx.user_id(),
+        ..Default::default()
     };
     let eff = Effect::SwitchScene(SceneSwitch {
         to_scene_id: TestScene2::ID,
+        params: None,
     });
 
     apply_effect(&routes, &TestScene, &ctx, &vp, &d, &sctx, eff)
@@ -515,6 +528,7 @@ async fn viewport_apply_view_prompt_sets_prompt_id_and_clears_reply_flag() {
         markup: None,
         parse_mode: None,
         disable_web_page_preview: None,
+        media: None,
     };
     vp.apply_view(
         &bot,
@@ -528,6 +542,7 @@ async fn viewport_apply_view_prompt_sets_prompt_id_and_clears_reply_flag() {
             state_json: Some("{}".into()),
             state_ref: None,
             ttl_secs: SNAP_TTL_SECS,
+            owner_id: None,
         }),
     )
     .await
@@ -539,3 +554,787 @@ async fn viewport_apply_view_prompt_sets_prompt_id_and_clears_reply_flag() {
 
     let _ = shutdown.send(());
 }
+
+#[derive(Default)]
+struct OrderScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum OrderState {
+    Empty,
+    Details(u64),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct OrderParams {
+    order_id: u64,
+}
+
+impl Scene for OrderScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "order";
+    const PREFIX: &'static str = "o";
+
+    type State = OrderState;
+    type Event = Event;
+
+    fn init(&self, _c: &Ctx) -> OrderState {
+        OrderState::Empty
+    }
+
+    fn init_with(&self, c: &Ctx, params: &serde_json::Value) -> OrderState {
+        match serde_json::from_value::<OrderParams>(params.clone()) {
+            Ok(p) => OrderState::Details(p.order_id),
+            Err(_) => self.init(c),
+        }
+    }
+
+    fn render(&self, _c: &Ctx, s: &OrderState) -> View {
+        View {
+            text: format!("{s:?}"),
+            markup: None,
+            parse_mode: None,
+            disable_web_page_preview: None,
+            media: None,
+        }
+    }
+
+    fn bindings(&self) -> Bindings<Event> {
+        Bindings {
+            msg: vec![],
+            cb: vec![],
+        }
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+#[tokio::test]
+async fn apply_effect_switch_scene_with_params_inits_target() {
+    let (addr, shutdown) = start_test_server().await;
+    let bot = Bot::with_client("TEST", reqwest::Client::new())
+        .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+    let vp = Viewport::new(NoopStore);
+    let d = dialogue();
+    let ctx = TestAppCtx {
+        bot,
+        chat: ChatId(1),
+    };
+
+    let routes = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<TestScene>())
+    .route(telegram_botkit::router::compose::Builder::scene::<OrderScene>())
+    .build()
+    .unwrap();
+
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+    let eff = Effect::SwitchScene(
+        SceneSwitch::to(OrderScene::ID).with_params(&OrderParams { order_id: 42 }),
+    );
+
+    apply_effect(&routes, &TestScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    let st = d.get_or_default().await.unwrap();
+    let mid = st.ui_get_last_action_message_id().expect("rendered");
+    assert_eq!(st.ui_get_active_scene_id().as_deref(), Some(OrderScene::ID));
+
+    // Snapshot persisted for the rendered
+    // message carries the param-built state.
+    let (restored, label) = telegram_botkit::router::core::restore_state(
+        &OrderScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), teloxide::types::MessageId(mid))),
+    )
+    .await;
+    assert_eq!(restored, OrderState::Details(42));
+    assert_eq!(label, "dialogue");
+
+    let _ = shutdown.send(());
+}
+
+fn back_callback() -> teloxide::types::CallbackQuery {
+    serde_json::from_value(serde_json::json!({
+        "id": "1",
+        "from": {"id": 1, "is_bot": false, "first_name": "u"},
+        "chat_instance": "ci",
+        "data": telegram_botkit::ui::callback::BACK,
+        "message": {
+            "message_id": 100,
+            "date": 0,
+            "chat": {"id": 1, "type": "private"},
+            "text": "menu"
+        }
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn apply_effect_push_then_back_restores_previous_scene() {
+    let (addr, shutdown) = start_test_server().await;
+    let bot = Bot::with_client("TEST", reqwest::Client::new())
+        .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+    let vp = Viewport::new(NoopStore);
+    let d = dialogue();
+    let ctx = TestAppCtx {
+        bot,
+        chat: ChatId(1),
+    };
+
+    let routes = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<TestScene>())
+    .route(telegram_botkit::router::compose::Builder::scene::<OrderScene>())
+    .build()
+    .unwrap();
+
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+    let eff = Effect::Push(OrderState::Details(7), SceneSwitch::to(TestScene::ID));
+
+    apply_effect(&routes, &OrderScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    let st = d.get_or_default().await.unwrap();
+    assert_eq!(st.ui_get_active_scene_id().as_deref(), Some(TestScene::ID));
+    assert_eq!(st.ui_get_nav_depth(), 1);
+
+    let router = telegram_botkit::router::RouterBuilder::new()
+        .with_routes(routes)
+        .build()
+        .unwrap();
+    let q = back_callback();
+
+    router
+        .handle(&ctx, &vp, &d, telegram_botkit::router::AppEvent::Cb(&q))
+        .await
+        .unwrap();
+
+    let st = d.get_or_default().await.unwrap();
+    let mid = st.ui_get_last_action_message_id().expect("rendered");
+    assert_eq!(st.ui_get_active_scene_id().as_deref(), Some(OrderScene::ID));
+    assert_eq!(st.ui_get_nav_depth(), 0);
+
+    let (restored, _) = telegram_botkit::router::core::restore_state(
+        &OrderScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), teloxide::types::MessageId(mid))),
+    )
+    .await;
+    assert_eq!(restored, OrderState::Details(7));
+
+    let _ = shutdown.send(());
+}
+
+struct LifecycleScene {
+    entered: Arc<std::sync::atomic::AtomicU32>,
+    left: Arc<std::sync::atomic::AtomicU32>,
+}
+
+impl Scene for LifecycleScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "lifecycle";
+    const PREFIX: &'static str = "lc";
+
+    type State = State;
+    type Event = Event;
+
+    fn init(&self, _c: &Ctx) -> State {
+        State::Root
+    }
+
+    fn render(&self, _c: &Ctx, _s: &State) -> View {
+        View {
+            text: "draft".into(),
+            markup: None,
+            parse_mode: None,
+            disable_web_page_preview: None,
+            media: None,
+        }
+    }
+
+    fn bindings(&self) -> Bindings<Event> {
+        Bindings {
+            msg: vec![],
+            cb: vec![],
+        }
+    }
+
+    fn on_enter(&self, _c: &Ctx, _s: &State) -> Vec<UiEffect> {
+        self.entered
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        vec![]
+    }
+
+    fn on_leave(&self, _c: &Ctx, _s: &State) -> Vec<UiEffect> {
+        self.left.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        vec![UiEffect::ClearPrompt]
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+#[tokio::test]
+async fn switch_scene_runs_enter_and_leave_hooks() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let (addr, shutdown) = start_test_server().await;
+    let bot = Bot::with_client("TEST", reqwest::Client::new())
+        .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+    let vp = Viewport::new(NoopStore);
+    let d = dialogue();
+    let ctx = TestAppCtx {
+        bot,
+        chat: ChatId(1),
+    };
+
+    let entered = Arc::new(AtomicU32::new(0));
+    let left = Arc::new(AtomicU32::new(0));
+    let routes = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<TestScene>())
+    .route(telegram_botkit::router::compose::Builder::scene_with(
+        LifecycleScene {
+            entered: entered.clone(),
+            left: left.clone(),
+        },
+    ))
+    .build()
+    .unwrap();
+
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+
+    let eff = Effect::SwitchScene(SceneSwitch::to(LifecycleScene::ID));
+    apply_effect(&routes, &TestScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    assert_eq!(entered.load(Ordering::SeqCst), 1);
+    assert_eq!(left.load(Ordering::SeqCst), 0);
+
+    // preset prompt owned by the draft
+    let mut s = d.get_or_default().await.unwrap();
+    s.ui_set_input_prompt_message_id(Some(111));
+    d.update(s).await.unwrap();
+
+    let eff = Effect::SwitchScene(SceneSwitch::to(TestScene::ID));
+    apply_effect(&routes, &TestScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    let st = d.get_or_default().await.unwrap();
+    assert_eq!(left.load(Ordering::SeqCst), 1);
+    assert!(st.ui_get_input_prompt_message_id().is_none());
+    assert_eq!(st.ui_get_active_scene_id().as_deref(), Some(TestScene::ID));
+
+    let _ = shutdown.send(());
+}
+
+#[derive(Default)]
+struct LoaderScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum LoaderState {
+    Idle,
+    Loading,
+    Done(u32),
+}
+
+#[derive(Clone, Debug)]
+enum LoaderEvent {
+    Loaded(u32),
+}
+
+impl Scene for LoaderScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "loader";
+    const PREFIX: &'static str = "ld";
+
+    type State = LoaderState;
+    type Event = LoaderEvent;
+
+    fn init(&self, _c: &Ctx) -> LoaderState {
+        LoaderState::Idle
+    }
+
+    fn render(&self, _c: &Ctx, s: &LoaderState) -> View {
+        View {
+            text: format!("{s:?}"),
+            markup: None,
+            parse_mode: None,
+            disable_web_page_preview: None,
+            media: None,
+        }
+    }
+
+    fn update(
+        &self,
+        _c: &Ctx,
+        _s: &LoaderState,
+        e: LoaderEvent,
+    ) -> Effect<LoaderState, LoaderEvent> {
+        match e {
+            LoaderEvent::Loaded(n) => Effect::Stay(LoaderState::Done(n), RenderPolicy::EditOrReply),
+        }
+    }
+
+    fn bindings(&self) -> Bindings<LoaderEvent> {
+        Bindings {
+            msg: vec![],
+            cb: vec![],
+        }
+    }
+}
+
+#[tokio::test]
+async fn apply_effect_cmd_feeds_event_back_into_update() {
+    let (addr, shutdown) = start_test_server().await;
+    let bot = Bot::with_client("TEST", reqwest::Client::new())
+        .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+    let vp = Viewport::new(NoopStore);
+    let d = dialogue();
+    let ctx = TestAppCtx {
+        bot,
+        chat: ChatId(1),
+    };
+
+    let routes = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<
+        LoaderScene,
+    >())
+    .build()
+    .unwrap();
+
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+    let eff = Effect::StayWithCmd(
+        LoaderState::Loading,
+        RenderPolicy::EditOrReply,
+        Cmd::perform(async {
+            tokio::task::yield_now().await;
+            LoaderEvent::Loaded(7)
+        }),
+    );
+
+    apply_effect(&routes, &LoaderScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    let st = d.get_or_default().await.unwrap();
+    let mid = st.ui_get_last_action_message_id().expect("rendered");
+    let (restored, _) = telegram_botkit::router::core::restore_state(
+        &LoaderScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), teloxide::types::MessageId(mid))),
+    )
+    .await;
+    assert_eq!(restored, LoaderState::Done(7));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn cmd_yields_its_event() {
+    let cmd = Cmd::perform(async { LoaderEvent::Loaded(5) });
+
+    assert!(matches!(cmd.run().await, LoaderEvent::Loaded(5)));
+}
+
+#[derive(Default)]
+struct MenuScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum MenuState {
+    Home,
+    Stats,
+    Bought(u32),
+    Found(String),
+    Screenshot(String),
+    Located,
+    Typed(String),
+}
+
+#[derive(Clone, Debug)]
+enum MenuEvent {
+    Stats,
+    Buy(u32),
+    Find(String),
+    Screenshot(String),
+    Located,
+    Typed(String),
+}
+
+impl Scene for MenuScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "menu";
+    const PREFIX: &'static str = "mn";
+
+    type State = MenuState;
+    type Event = MenuEvent;
+
+    fn init(&self, _c: &Ctx) -> MenuState {
+        MenuState::Home
+    }
+
+    fn render(&self, _c: &Ctx, s: &MenuState) -> View {
+        View {
+            text: format!("{s:?}"),
+            markup: Some(telegram_botkit::ui::keyboard::reply_rows(vec![vec!["📊 Stats"]]).into()),
+            parse_mode: None,
+            disable_web_page_preview: None,
+            media: None,
+        }
+    }
+
+    fn update(&self, _c: &Ctx, _s: &MenuState, e: MenuEvent) -> Effect<MenuState, MenuEvent> {
+        match e {
+            MenuEvent::Stats => Effect::Stay(MenuState::Stats, RenderPolicy::EditOrReply),
+            MenuEvent::Buy(n) => Effect::Stay(MenuState::Bought(n), RenderPolicy::EditOrReply),
+            MenuEvent::Find(q) => Effect::Stay(MenuState::Found(q), RenderPolicy::EditOrReply),
+            MenuEvent::Screenshot(id) => {
+                Effect::Stay(MenuState::Screenshot(id), RenderPolicy::EditOrReply)
+            }
+            MenuEvent::Located => Effect::Stay(MenuState::Located, RenderPolicy::EditOrReply),
+            MenuEvent::Typed(t) => Effect::Stay(MenuState::Typed(t), RenderPolicy::EditOrReply),
+        }
+    }
+
+    fn bindings(&self) -> Bindings<MenuEvent> {
+        Bindings {
+            msg: vec![
+                MsgBinding {
+                    pattern: MsgPattern::Button("📊 Stats"),
+                    to_event: |_, _| Some(MenuEvent::Stats),
+                },
+                MsgBinding {
+                    pattern: MsgPattern::Command("buy"),
+                    to_event: |_, m| Some(MenuEvent::Buy(m.argv().next()?.parse().ok()?)),
+                },
+                MsgBinding {
+                    pattern: MsgPattern::Regex(r"^find (?P<q>\w+)$"),
+                    to_event: |_, m| Some(MenuEvent::Find(m.name("q")?.to_string())),
+                },
+                // Ahead of the media bindings on purpose
+                MsgBinding {
+                    pattern: MsgPattern::AnyText,
+                    to_event: |_, m| Some(MenuEvent::Typed(m.args().to_string())),
+                },
+                MsgBinding {
+                    pattern: MsgPattern::Photo,
+                    to_event: |m, _| {
+                        let id = &m.photo()?.last()?.file.id;
+                        Some(MenuEvent::Screenshot(id.to_string()))
+                    },
+                },
+                MsgBinding {
+                    pattern: MsgPattern::Location,
+                    to_event: |_, _| Some(MenuEvent::Located),
+                },
+            ],
+            cb: vec![],
+        }
+    }
+}
+
+fn text_message(text: &str) -> Message {
+    serde_json::from_value(serde_json::json!({
+        "message_id": 5,
+        "date": 0,
+        "chat": {"id": 1, "type": "private"},
+        "from": {"id": 1, "is_bot": false, "first_name": "u"},
+        "text": text,
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn reply_keyboard_button_routes_to_binding() {
+    use telegram_botkit::router::compose::RouterDispatch;
+
+    let (addr, shutdown) = start_test_server().await;
+    let bot = Bot::with_client("TEST", reqwest::Client::new())
+        .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+    let vp = Viewport::new(NoopStore);
+    let d = dialogue();
+    let ctx = TestAppCtx {
+        bot,
+        chat: ChatId(1),
+    };
+
+    let routes = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<MenuScene>())
+    .build()
+    .unwrap();
+
+    let other = text_message("hello");
+    assert!(
+        !routes
+            .handle_msg(Some(MenuScene::ID), &ctx, &vp, &d, &other)
+            .await
+            .unwrap()
+    );
+
+    let press = text_message("📊 Stats");
+    assert!(
+        routes
+            .handle_msg(Some(MenuScene::ID), &ctx, &vp, &d, &press)
+            .await
+            .unwrap()
+    );
+
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+    let st = d.get_or_default().await.unwrap();
+    let mid = st.ui_get_last_action_message_id().expect("rendered");
+    let (restored, _) = telegram_botkit::router::core::restore_state(
+        &MenuScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), teloxide::types::MessageId(mid))),
+    )
+    .await;
+    assert_eq!(restored, MenuState::Stats);
+
+    let _ = shutdown.send(());
+}
+
+async fn menu_state_after(text: &str) -> (bool, MenuState) {
+    menu_state_for(text_message(text), false).await
+}
+
+async fn menu_state_for(m: Message, prompt: bool) -> (bool, MenuState) {
+    use telegram_botkit::router::compose::RouterDispatch;
+
+    let (addr, shutdown) = start_test_server().await;
+    let bot = Bot::with_client("TEST", reqwest::Client::new())
+        .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+    let vp = Viewport::new(NoopStore);
+    let d = dialogue();
+    let ctx = TestAppCtx {
+        bot,
+        chat: ChatId(1),
+    };
+
+    let routes = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<MenuScene>())
+    .build()
+    .unwrap();
+
+    if prompt {
+        let mut s = d.get_or_default().await.unwrap();
+        s.ui_set_input_prompt_message_id(Some(50));
+        d.update(s).await.unwrap();
+    }
+
+    let handled = routes
+        .handle_msg(Some(MenuScene::ID), &ctx, &vp, &d, &m)
+        .await
+        .unwrap();
+
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+    let mid = d
+        .get_or_default()
+        .await
+        .unwrap()
+        .ui_get_last_action_message_id();
+    let (restored, _) = telegram_botkit::router::core::restore_state(
+        &MenuScene,
+        &vp,
+        &d,
+        &sctx,
+        mid.map(|mid| (ChatId(1), teloxide::types::MessageId(mid))),
+    )
+    .await;
+
+    let _ = shutdown.send(());
+
+    (handled, restored)
+}
+
+#[tokio::test]
+async fn command_binding_gets_arguments() {
+    assert_eq!(
+        menu_state_after("/buy 3").await,
+        (true, MenuState::Bought(3))
+    );
+    assert_eq!(
+        menu_state_after("/buy@botkit_bot  7").await,
+        (true, MenuState::Bought(7))
+    );
+    assert!(!menu_state_after("/buy@other_bot 7").await.0);
+    assert!(!menu_state_after("/buyer 7").await.0);
+    assert!(!menu_state_after("/buy").await.0);
+}
+
+#[tokio::test]
+async fn regex_binding_gets_captures() {
+    assert_eq!(
+        menu_state_after("find apples").await,
+        (true, MenuState::Found("apples".into()))
+    );
+    assert!(!menu_state_after("find two words").await.0);
+}
+
+fn media_message(media: serde_json::Value) -> Message {
+    let mut v = serde_json::json!({
+        "message_id": 5,
+        "date": 0,
+        "chat": {"id": 1, "type": "private"},
+        "from": {"id": 1, "is_bot": false, "first_name": "u"},
+    });
+    v.as_object_mut()
+        .unwrap()
+        .extend(media.as_object().unwrap().clone());
+
+    serde_json::from_value(v).unwrap()
+}
+
+#[tokio::test]
+async fn media_bindings_need_active_prompt() {
+    let photo = || {
+        media_message(serde_json::json!({
+            "photo": [{
+                "file_id": "shot",
+                "file_unique_id": "u1",
+                "width": 10,
+                "height": 10,
+            }],
+            "caption": "my screen",
+        }))
+    };
+    let location = media_message(serde_json::json!({
+        "location": {"latitude": 1.5, "longitude": 2.5},
+    }));
+
+    assert!(!menu_state_for(photo(), false).await.0);
+    assert_eq!(
+        menu_state_for(photo(), true).await,
+        (true, MenuState::Screenshot("shot".into()))
+    );
+    assert_eq!(
+        menu_state_for(location, true).await,
+        (true, MenuState::Located)
+    );
+    assert_eq!(
+        menu_state_for(text_message("hi"), true).await,
+        (true, MenuState::Typed("hi".into()))
+    );
+}
+
+#[derive(Default)]
+struct BadRegexScene;
+
+impl Scene for BadRegexScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "bad_regex";
+    const PREFIX: &'static str = "br";
+
+    type State = MenuState;
+    type Event = MenuEvent;
+
+    fn init(&self, _c: &Ctx) -> MenuState {
+        MenuState::Home
+    }
+
+    fn render(&self, c: &Ctx, s: &MenuState) -> View {
+        MenuScene.render(c, s)
+    }
+
+    fn update(&self, _c: &Ctx, s: &MenuState, _e: MenuEvent) -> Effect<MenuState, MenuEvent> {
+        Effect::Stay(s.clone(), RenderPolicy::EditOrReply)
+    }
+
+    fn bindings(&self) -> Bindings<MenuEvent> {
+        Bindings {
+            msg: vec![MsgBinding {
+                pattern: MsgPattern::Regex("(unclosed"),
+                to_event: |_, _| None,
+            }],
+            cb: vec![],
+        }
+    }
+}
+
+#[test]
+fn invalid_regex_fails_build() {
+    let res = telegram_botkit::router::compose::Builder::<
+        TestAppCtx,
+        SimpleSession,
+        InMemStorage<SimpleSession>,
+        NoopStore,
+    >::new()
+    .route(telegram_botkit::router::compose::Builder::scene::<
+        BadRegexScene,
+    >())
+    .build();
+
+    assert!(matches!(
+        res,
+        Err(telegram_botkit::router::compose::ComposeError::InvalidRegex("(unclosed", _))
+    ));
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use telegram_botkit::router::compose::{Builder, RouterDispatch};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::SimpleSession;
+use telegram_botkit::viewport::{MessageMeta, Viewport, store::Store};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::ChatId;
+
+use common::{Log, MockApi, TestAppCtx, callback};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct MapStore(Arc<Mutex<HashMap<(i64, i32), MessageMeta>>>);
+
+#[async_trait::async_trait]
+impl Store for MapStore {
+    async fn save(&self, chat: ChatId, mid: i32, meta: MessageMeta) -> anyhow::Result<()> {
+        self.0.lock().unwrap().insert((chat.0, mid), meta);
+        Ok(())
+    }
+
+    async fn load(&self, chat: ChatId, mid: i32) -> anyhow::Result<Option<MessageMeta>> {
+        Ok(self.0.lock().unwrap().get(&(chat.0, mid)).cloned())
+    }
+}
+
+// Stands in for a database or RPC client
+#[derive(Default)]
+struct Counter {
+    hits: AtomicU32,
+}
+
+impl Counter {
+    async fn bump(&self) -> u32 {
+        tokio::task::yield_now().await;
+        self.hits.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum State {
+    Count(u32),
+}
+
+#[derive(Clone, Debug)]
+enum Event {
+    Inc,
+}
+
+struct AsyncCounterScene {
+    svc: Arc<Counter>,
+}
+
+impl Scene for AsyncCounterScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "async_counter";
+    const PREFIX: &'static str = "ac";
+
+    type State = State;
+    type Event = Event;
+
+    fn init(&self, _ctx: &Ctx) -> Self::State {
+        State::Count(0)
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &Self::State) -> View {
+        let State::Count(n) = s;
+
+        View::text(format!("count: {n}"))
+    }
+
+    fn update_async<'a>(
+        &'a self,
+        _ctx: &'a Ctx,
+        _state: Self::State,
+        event: Self::Event,
+    ) -> SceneFuture<'a, Effect<Self::State, Self::Event>> {
+        Box::pin(async move {
+            match event {
+                Event::Inc => {
+                    let n = self.svc.bump().await;
+                    Effect::Stay(State::Count(n), RenderPolicy::EditOrReply)
+                }
+            }
+        })
+    }
+
+    fn bindings(&self) -> Bindings<Self::Event> {
+        Bindings {
+            msg: vec![],
+            cb: vec![CbBinding::new(CbKey::Exact("ac:inc"), |q| {
+                (q.data.as_deref() == Some("ac:inc")).then_some(Event::Inc)
+            })],
+        }
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+#[tokio::test]
+async fn async_update_is_driven_by_router() {
+    let (bot, shutdown) = MockApi::default().start().await;
+    let store = MapStore::default();
+    let vp = Viewport::new(store.clone());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let svc = Arc::new(Counter::default());
+    let routes = Builder::<TestAppCtx, SimpleSession, InMemStorage<SimpleSession>, MapStore>::new()
+        .route(Builder::scene_with(AsyncCounterScene { svc: svc.clone() }))
+        .build()
+        .unwrap();
+
+    let handled = routes
+        .handle_cb(&ctx, &vp, &d, &callback("ac:inc"))
+        .await
+        .unwrap();
+    assert!(handled);
+
+    routes
+        .handle_cb(&ctx, &vp, &d, &callback("ac:inc"))
+        .await
+        .unwrap();
+
+    assert_eq!(svc.hits.load(Ordering::SeqCst), 2);
+
+    let meta = vp.load_meta(ChatId(1), 100).await.unwrap().expect("meta");
+    assert_eq!(meta.scene_id, AsyncCounterScene::ID);
+    assert_eq!(
+        meta.state_json.as_deref(),
+        Some(serde_json::to_string(&State::Count(2)).unwrap().as_str())
+    );
+
+    let _ = shutdown.send(());
+}
+
+struct LoaderScene {
+    log: Log,
+}
+
+#[derive(Clone, Debug)]
+enum LoaderEvent {
+    Load,
+    Loaded,
+}
+
+impl Scene for LoaderScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "loader";
+    const PREFIX: &'static str = "ld";
+
+    type State = String;
+    type Event = LoaderEvent;
+
+    fn init(&self, _ctx: &Ctx) -> String {
+        "idle".into()
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &String) -> View {
+        View::text(s.clone())
+    }
+
+    fn update(&self, _ctx: &Ctx, _s: &String, e: LoaderEvent) -> Effect<String, LoaderEvent> {
+        match e {
+            LoaderEvent::Load => {
+                let log = self.log.clone();
+                let cmd = Cmd::perform(async move {
+                    log.lock().unwrap().push("cmd".into());
+                    LoaderEvent::Loaded
+                });
+
+                Effect::StayWithCmd("Loading…".into(), RenderPolicy::EditOrReply, cmd)
+            }
+            LoaderEvent::Loaded => Effect::Stay("Done".into(), RenderPolicy::EditOrReply),
+        }
+    }
+
+    fn bindings(&self) -> Bindings<LoaderEvent> {
+        Bindings {
+            msg: vec![],
+            cb: vec![CbBinding::new(CbKey::Exact("ld:load"), |_| {
+                Some(LoaderEvent::Load)
+            })],
+        }
+    }
+}
+
+#[tokio::test]
+async fn callback_is_answered_before_command_runs() {
+    let api = MockApi::default();
+    let log = api.calls.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MapStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::<TestAppCtx, SimpleSession, InMemStorage<SimpleSession>, MapStore>::new()
+        .route(Builder::scene_with(LoaderScene { log: log.clone() }))
+        .build()
+        .unwrap();
+
+    routes
+        .handle_cb(&ctx, &vp, &d, &callback("ld:load"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "AnswerCallbackQuery",
+            "SendMessage",
+            "cmd",
+            "EditMessageText"
+        ]
+    );
+
+    let _ = shutdown.send(());
+}
@@ -0,0 +1,444 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::core::restore_state;
+use telegram_botkit::router::{AppEvent, Fallback, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::SimpleSession;
+use telegram_botkit::ui::callback::{CallbackSigner, PAYLOAD_TOKEN};
+use telegram_botkit::viewport::store::{MemoryStore, NoopStore};
+use telegram_botkit::viewport::{MetaSpec, Viewport};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, MessageId};
+
+use common::{Log, MockApi, TestAppCtx, callback, text_message};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct NoteScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum State {
+    Empty,
+    Saved(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Save(String),
+}
+
+impl ActionCodec for Event {
+    fn encode(&self, prefix: &str) -> String {
+        match self {
+            Event::Save(note) => format!("{prefix}:save:{note}"),
+        }
+    }
+
+    fn decode(prefix: &str, s: &str) -> Option<Self> {
+        let note = s.strip_prefix(prefix)?.strip_prefix(":save:")?;
+        Some(Event::Save(note.to_string()))
+    }
+}
+
+impl Scene for NoteScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "note";
+    const PREFIX: &'static str = "n";
+
+    type State = State;
+    type Event = Event;
+
+    fn init(&self, _ctx: &Ctx) -> State {
+        State::Empty
+    }
+
+    fn render(&self, _ctx: &Ctx, _s: &State) -> View {
+        let save = Event::Save(LONG_NOTE.into()).encode(Self::PREFIX);
+
+        View::text("note").with_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback("Save", save),
+        ]]))
+    }
+
+    fn update(&self, _ctx: &Ctx, _s: &State, e: Event) -> Effect<State, Event> {
+        match e {
+            Event::Save(note) => Effect::Stay(State::Saved(note), RenderPolicy::EditOrReply),
+        }
+    }
+}
+
+const LONG_NOTE: &str = "a note that is far too long to fit into telegram callback data limits";
+
+fn sent_token(bodies: &Log) -> String {
+    let bodies = bodies.lock().unwrap();
+    let body = bodies
+        .iter()
+        .find(|b| b.starts_with("SendMessage"))
+        .unwrap();
+    let start = body.find(PAYLOAD_TOKEN).unwrap();
+    let end = start + body[start..].find('"').unwrap();
+
+    body[start..end].to_string()
+}
+
+#[tokio::test]
+async fn oversized_callback_is_stored_behind_token() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default()).with_payload_tokens(true);
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot.clone(), ChatId(1));
+    let sctx = Ctx {
+        user_id: 1,
+        ..Default::default()
+    };
+
+    let view = NoteScene.render(&sctx, &State::Empty);
+    vp.apply_view(&bot, ChatId(1), &d, &view, RenderPolicy::EditOrReply, None)
+        .await
+        .unwrap();
+
+    let token = sent_token(&bodies);
+    assert!(token.len() <= CALLBACK_DATA_MAX);
+    assert!(!bodies.lock().unwrap()[0].contains(LONG_NOTE));
+    assert_eq!(
+        vp.load_payload(&token).await.unwrap().as_deref(),
+        Some(format!("n:save:{LONG_NOTE}").as_str())
+    );
+
+    let routes = Builder::new()
+        .route(Builder::scene::<NoteScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback(&token)))
+        .await
+        .unwrap();
+
+    let (st, _) = restore_state(
+        &NoteScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), MessageId(100))),
+    )
+    .await;
+    assert_eq!(st, State::Saved(LONG_NOTE.into()));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn unknown_payload_token_is_answered_with_alert() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default()).with_payload_tokens(true);
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<NoteScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+    let token = format!("{PAYLOAD_TOKEN}0123456789abcdef01234567");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback(&token)))
+        .await
+        .unwrap();
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 1);
+    assert!(bodies[0].starts_with("AnswerCallbackQuery"));
+    assert!(bodies[0].contains("no longer active"));
+
+    let _ = shutdown.send(());
+}
+
+#[test]
+fn signer_rejects_tampered_data() {
+    let signer = CallbackSigner::new("secret");
+    let signed = signer.sign("n:save:x");
+    assert_eq!(signed.len(), "n:save:x".len() + CallbackSigner::OVERHEAD);
+    assert_eq!(signer.verify(&signed), Some("n:save:x"));
+
+    let forged = signed.replacen("n:save:x", "n:save:y", 1);
+    assert_eq!(signer.verify(&forged), None);
+    assert_eq!(signer.verify("n:save:x"), None);
+    assert_eq!(CallbackSigner::new("other").verify(&signed), None);
+}
+
+#[tokio::test]
+async fn signed_callbacks_route_and_forged_are_dropped() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default()).with_callback_secret("secret");
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+    let sctx = Ctx {
+        user_id: 1,
+        ..Default::default()
+    };
+
+    let routes = Builder::new()
+        .route(Builder::scene::<NoteScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_callback_secret("secret")
+        .build()
+        .unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback("n:save:forged")))
+        .await
+        .unwrap();
+    {
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].starts_with("AnswerCallbackQuery"));
+    }
+
+    let signed = CallbackSigner::new("secret").sign("n:save:ok");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback(&signed)))
+        .await
+        .unwrap();
+
+    let (st, _) = restore_state(
+        &NoteScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), MessageId(100))),
+    )
+    .await;
+    assert_eq!(st, State::Saved("ok".into()));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn viewport_signs_scene_buttons_only() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default()).with_callback_secret("secret");
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+
+    let signed = CallbackSigner::new("secret").sign("n:save:ok");
+    let view = View {
+        text: "note".into(),
+        markup: Some(
+            InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback("Save", "n:save:ok"),
+                InlineKeyboardButton::callback("Back", telegram_botkit::ui::callback::BACK),
+            ]])
+            .into(),
+        ),
+        parse_mode: None,
+        disable_web_page_preview: None,
+        media: None,
+    };
+    vp.apply_view(&bot, ChatId(1), &d, &view, RenderPolicy::EditOrReply, None)
+        .await
+        .unwrap();
+
+    let body = bodies.lock().unwrap().pop().unwrap();
+    assert!(body.contains(&format!("\"{signed}\"")));
+    assert!(body.contains("\"ui:back\""));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn signed_data_over_limit_falls_back_to_keyed_token() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+
+    // Fits unsigned, not with the signature
+    let data = format!("n:save:{}", "x".repeat(50));
+    assert!(data.len() <= CALLBACK_DATA_MAX);
+    let view = View {
+        text: "note".into(),
+        markup: Some(
+            InlineKeyboardMarkup::new([[InlineKeyboardButton::callback("Save", data.clone())]])
+                .into(),
+        ),
+        parse_mode: None,
+        disable_web_page_preview: None,
+        media: None,
+    };
+
+    let mut tokens = Vec::new();
+    for secret in ["a", "b"] {
+        let vp = Viewport::new(MemoryStore::default()).with_callback_secret(secret);
+        vp.apply_view(&bot, ChatId(1), &d, &view, RenderPolicy::SendNew, None)
+            .await
+            .unwrap();
+
+        let body = bodies.lock().unwrap().pop().unwrap();
+        let start = body.find(PAYLOAD_TOKEN).unwrap();
+        let token = &body[start..start + body[start..].find('"').unwrap()];
+        assert!(token.len() <= CALLBACK_DATA_MAX);
+
+        assert_eq!(vp.load_payload(token).await.unwrap(), Some(data.clone()));
+        tokens.push(token.to_string());
+    }
+
+    // Unsigned like other `ui:` data, but keyed by the secret
+    assert_ne!(tokens[0], tokens[1]);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn signed_data_over_limit_without_payload_store_is_sent_unsigned() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(NoopStore).with_callback_secret("secret");
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+
+    let data = format!("n:save:{}", "x".repeat(50));
+    let view = View::text("note").with_markup(InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("Save", data.clone()),
+        InlineKeyboardButton::callback("Ok", "n:save:ok"),
+    ]]));
+
+    // The menu still goes out
+    vp.apply_view(&bot, ChatId(1), &d, &view, RenderPolicy::SendNew, None)
+        .await
+        .unwrap();
+
+    let body = bodies.lock().unwrap().pop().unwrap();
+    assert!(body.contains(&format!("\"{data}\"")));
+    assert!(!body.contains(PAYLOAD_TOKEN));
+    let signed = CallbackSigner::new("secret").sign("n:save:ok");
+    assert!(body.contains(&format!("\"{signed}\"")));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn unhandled_hook_can_alert_or_rerender() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    vp.save_meta_public(
+        ChatId(1),
+        100,
+        MetaSpec {
+            state_json: Some(serde_json::to_string(&State::Saved("x".into())).unwrap()),
+            ..MetaSpec::new(NoteScene::ID, NoteScene::VERSION)
+        },
+    )
+    .await
+    .unwrap();
+
+    let routes = Builder::new()
+        .route(Builder::scene::<NoteScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .on_unhandled(|ev, meta| match (ev, meta) {
+            (AppEvent::Cb(q), _) if q.data.as_deref() == Some("old:alert") => {
+                Fallback::Alert("Menu expired".into())
+            }
+            (_, Some(meta)) if meta.scene_id == NoteScene::ID => Fallback::Rerender,
+            _ => Fallback::Default,
+        })
+        .build()
+        .unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback("old:alert")))
+        .await
+        .unwrap();
+    {
+        let mut bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].starts_with("AnswerCallbackQuery"));
+        assert!(bodies[0].contains("Menu expired"));
+        bodies.clear();
+    }
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback("old:menu")))
+        .await
+        .unwrap();
+    {
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[0].starts_with("EditMessageText"));
+        assert!(bodies[1].starts_with("AnswerCallbackQuery"));
+        assert!(!bodies[1].contains("no longer active"));
+    }
+
+    let (st, _) = restore_state(
+        &NoteScene,
+        &vp,
+        &d,
+        &Ctx::default(),
+        Some((ChatId(1), MessageId(100))),
+    )
+    .await;
+    assert_eq!(st, State::Saved("x".into()));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn unhandled_message_can_switch_scene() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<NoteScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_clear_noise(false)
+        .on_unhandled(|_, _| Fallback::SwitchTo(NoteScene::ID))
+        .build()
+        .unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("hello?")))
+        .await
+        .unwrap();
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 1);
+    assert!(bodies[0].starts_with("SendMessage"));
+    assert!(bodies[0].contains("\"note\""));
+
+    let _ = shutdown.send(());
+}
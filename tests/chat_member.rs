@@ -0,0 +1,264 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::{AppEvent, MemberEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::SimpleSession;
+use telegram_botkit::viewport::store::{MemoryStore, Store};
+use telegram_botkit::viewport::{MessageMeta, Viewport};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::{ChatId, ChatMemberUpdated};
+
+use common::{MockApi, TestAppCtx, text_message};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct HomeScene;
+
+impl Scene for HomeScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "home";
+    const PREFIX: &'static str = "hm";
+
+    type State = ();
+    type Event = ();
+
+    fn init(&self, _ctx: &Ctx) {}
+
+    fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+        View::text("home")
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+type Routes = telegram_botkit::router::compose::Routes<
+    TestAppCtx,
+    SimpleSession,
+    InMemStorage<SimpleSession>,
+    MemoryStore,
+>;
+
+fn routes() -> Routes {
+    Builder::new()
+        .route(Builder::scene::<HomeScene>())
+        .build()
+        .unwrap()
+}
+
+/// Counts round trips for the unreachable flag.
+#[derive(Clone, Default)]
+struct CountingStore {
+    inner: MemoryStore,
+    flag_calls: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl Store for CountingStore {
+    async fn save(&self, chat: ChatId, mid: i32, meta: MessageMeta) -> anyhow::Result<()> {
+        self.inner.save(chat, mid, meta).await
+    }
+
+    async fn load(&self, chat: ChatId, mid: i32) -> anyhow::Result<Option<MessageMeta>> {
+        self.inner.load(chat, mid).await
+    }
+
+    async fn load_unreachable(&self, chat: ChatId) -> anyhow::Result<Option<bool>> {
+        self.flag_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.load_unreachable(chat).await
+    }
+
+    async fn save_unreachable(&self, chat: ChatId, v: bool) -> anyhow::Result<()> {
+        self.flag_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.save_unreachable(chat, v).await
+    }
+}
+
+fn my_member(status: serde_json::Value) -> ChatMemberUpdated {
+    let bot = serde_json::json!({"id": 99, "is_bot": true, "first_name": "bot"});
+    let mut new = status;
+    new["user"] = bot.clone();
+
+    serde_json::from_value(serde_json::json!({
+        "chat": {"id": 1, "type": "private", "first_name": "u"},
+        "from": {"id": 1, "is_bot": false, "first_name": "u"},
+        "date": 0,
+        "old_chat_member": {"user": bot, "status": "member"},
+        "new_chat_member": new,
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn blocked_chat_skips_renders_until_unblocked() {
+    let api = MockApi::default();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage.clone(), ChatId(1));
+    let ctx = TestAppCtx::new(bot.clone(), ChatId(1));
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let router = RouterBuilder::new()
+        .with_routes(routes())
+        .on_member_update({
+            let seen = seen.clone();
+            move |ev| {
+                let seen = seen.clone();
+                async move {
+                    if let MemberEvent::Bot { reachable, .. } = ev {
+                        seen.lock().unwrap().push(reachable);
+                    }
+                }
+            }
+        })
+        .build()
+        .unwrap();
+
+    let kicked = my_member(serde_json::json!({"status": "kicked", "until_date": 0}));
+    router
+        .handle(&ctx, &vp, &d, AppEvent::MyChatMember(&kicked))
+        .await
+        .unwrap();
+    assert!(vp.is_unreachable(ChatId(1)).await);
+
+    // The flag is per chat, not per member dialogue
+    let other = Dialogue::new(storage, ChatId(2));
+    for d in [&d, &other] {
+        vp.apply_view(
+            &bot,
+            ChatId(1),
+            d,
+            &View::text("home"),
+            RenderPolicy::EditOrReply,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(api.calls.lock().unwrap().len(), 0);
+
+    let member = my_member(serde_json::json!({"status": "member"}));
+    router
+        .handle(&ctx, &vp, &d, AppEvent::MyChatMember(&member))
+        .await
+        .unwrap();
+    assert!(!vp.is_unreachable(ChatId(1)).await);
+
+    vp.apply_view(
+        &bot,
+        ChatId(1),
+        &d,
+        &View::text("home"),
+        RenderPolicy::EditOrReply,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(api.calls.lock().unwrap().len(), 1);
+    assert_eq!(*seen.lock().unwrap(), [false, true]);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn forbidden_send_marks_chat_unreachable() {
+    let api = MockApi::replying(|_, _| {
+        serde_json::json!({
+            "ok": false,
+            "error_code": 403,
+            "description": "Forbidden: bot was blocked by the user"
+        })
+    });
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+
+    let res = vp
+        .apply_view(
+            &bot,
+            ChatId(1),
+            &d,
+            &View::text("home"),
+            RenderPolicy::EditOrReply,
+            None,
+        )
+        .await;
+    assert!(res.is_err());
+    assert!(vp.is_unreachable(ChatId(1)).await);
+
+    // Incoming messages make the chat reachable again
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+    let router = RouterBuilder::new()
+        .with_routes(routes())
+        .with_clear_noise(false)
+        .build()
+        .unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("hi")))
+        .await
+        .unwrap();
+    assert!(!vp.is_unreachable(ChatId(1)).await);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn reachable_flag_is_read_once_per_chat() {
+    let api = MockApi::default();
+    let (bot, shutdown) = api.start().await;
+    let store = CountingStore::default();
+    let flag_calls = store.flag_calls.clone();
+    let vp = Viewport::new(store);
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot.clone(), ChatId(1));
+    let router = RouterBuilder::new()
+        .with_routes(
+            Builder::new()
+                .route(Builder::scene::<HomeScene>())
+                .build()
+                .unwrap(),
+        )
+        .with_clear_noise(false)
+        .build()
+        .unwrap();
+
+    for _ in 0..3 {
+        router
+            .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("hi")))
+            .await
+            .unwrap();
+        vp.apply_view(
+            &bot,
+            ChatId(1),
+            &d,
+            &View::text("home"),
+            RenderPolicy::EditOrReply,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+    assert_eq!(flag_calls.load(Ordering::SeqCst), 1);
+
+    // Changes still reach the store
+    let kicked = my_member(serde_json::json!({"status": "kicked", "until_date": 0}));
+    router
+        .handle(&ctx, &vp, &d, AppEvent::MyChatMember(&kicked))
+        .await
+        .unwrap();
+    assert_eq!(flag_calls.load(Ordering::SeqCst), 2);
+
+    let _ = shutdown.send(());
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use telegram_botkit::router::compose::{Builder, ComposeError, Routes};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::SimpleSession;
+use telegram_botkit::viewport::store::NoopStore;
+
+use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::types::BotCommandScope;
+
+use common::{MockApi, TestAppCtx};
+
+type TestBuilder = Builder<TestAppCtx, SimpleSession, InMemStorage<SimpleSession>, NoopStore>;
+
+type TestRoutes = Routes<TestAppCtx, SimpleSession, InMemStorage<SimpleSession>, NoopStore>;
+
+fn command(name: &'static str) -> MsgBinding<()> {
+    MsgBinding::new(MsgPattern::Command(name), |_, _| Some(()))
+}
+
+macro_rules! command_scene {
+    ($name:ident, $id:literal, $prefix:literal, [$($cmd:literal),*]) => {
+        #[derive(Default)]
+        struct $name;
+
+        impl Scene for $name {
+            const VERSION: u16 = 1;
+            const ID: &'static str = $id;
+            const PREFIX: &'static str = $prefix;
+
+            type State = ();
+            type Event = ();
+
+            fn init(&self, _ctx: &Ctx) {}
+
+            fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+                View::text("shop")
+            }
+
+            fn bindings(&self) -> Bindings<()> {
+                Bindings {
+                    msg: vec![$(command($cmd)),*],
+                    cb: vec![],
+                }
+            }
+
+            fn update(&self, _ctx: &Ctx, _s: &Self::State, _e: Self::Event) -> Effect<Self::State, Self::Event> {
+                Effect::Noop
+            }
+        }
+    };
+}
+
+command_scene!(ShopScene, "shop", "s", ["buy", "/buy", "cart"]);
+command_scene!(AdminScene, "admin", "a", ["ban"]);
+command_scene!(OtherShopScene, "other_shop", "o", ["/buy"]);
+command_scene!(BadNameScene, "bad_name", "b", ["Buy"]);
+
+fn routes() -> TestRoutes {
+    TestBuilder::new()
+        .route(
+            TestBuilder::scene::<ShopScene>()
+                .describe("buy", "Buy an item")
+                .describe_in("buy", "ru", "Купить товар")
+                .describe("/cart", "Show the cart"),
+        )
+        .route(
+            TestBuilder::scene::<AdminScene>()
+                .command_scope(BotCommandScope::AllChatAdministrators),
+        )
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn registry_lists_bound_commands_once() {
+    let routes = routes();
+    let names: Vec<_> = routes
+        .commands()
+        .iter()
+        .map(|c| (c.command, c.scene_id))
+        .collect();
+    assert_eq!(names, [("buy", "shop"), ("cart", "shop"), ("ban", "admin")]);
+
+    let buy = &routes.commands()[0];
+    assert_eq!(
+        buy.descriptions,
+        [(None, "Buy an item"), (Some("ru"), "Купить товар")]
+    );
+    assert_eq!(
+        routes.commands()[2].scope,
+        Some(BotCommandScope::AllChatAdministrators)
+    );
+}
+
+#[test]
+fn duplicate_command_fails_build() {
+    let res = TestBuilder::new()
+        .route(TestBuilder::scene::<ShopScene>())
+        .route(TestBuilder::scene::<OtherShopScene>())
+        .build();
+
+    assert!(matches!(res, Err(ComposeError::DuplicateCommand("buy"))));
+}
+
+#[test]
+fn invalid_command_name_fails_build() {
+    let res = TestBuilder::new()
+        .route(TestBuilder::scene::<BadNameScene>())
+        .build();
+
+    assert!(matches!(res, Err(ComposeError::InvalidCommand("Buy"))));
+}
+
+#[tokio::test]
+async fn publish_sets_one_list_per_language() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+
+    routes().publish_commands(&bot).await.unwrap();
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 2);
+    assert!(bodies.iter().all(|b| b.starts_with("SetMyCommands")));
+
+    // Undescribed admin command isn't published
+    let default: serde_json::Value =
+        serde_json::from_str(bodies[0].trim_start_matches("SetMyCommands ")).unwrap();
+    assert_eq!(
+        default,
+        serde_json::json!({"commands": [
+            {"command": "buy", "description": "Buy an item"},
+            {"command": "cart", "description": "Show the cart"},
+        ]})
+    );
+
+    // Missing translations fall back to the default description
+    let ru: serde_json::Value =
+        serde_json::from_str(bodies[1].trim_start_matches("SetMyCommands ")).unwrap();
+    assert_eq!(
+        ru,
+        serde_json::json!({"language_code": "ru", "commands": [
+            {"command": "buy", "description": "Купить товар"},
+            {"command": "cart", "description": "Show the cart"},
+        ]})
+    );
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn scoped_lists_include_unscoped_commands() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+
+    let routes = TestBuilder::new()
+        .route(TestBuilder::scene::<ShopScene>().describe("buy", "Buy an item"))
+        .route(
+            TestBuilder::scene::<AdminScene>()
+                .describe("ban", "Ban a user")
+                .command_scope(BotCommandScope::AllChatAdministrators),
+        )
+        .build()
+        .unwrap();
+    routes.publish_commands(&bot).await.unwrap();
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies.len(), 2);
+
+    // Admins would otherwise lose /buy
+    let admin: serde_json::Value =
+        serde_json::from_str(bodies[1].trim_start_matches("SetMyCommands ")).unwrap();
+    assert_eq!(
+        admin,
+        serde_json::json!({
+            "scope": {"type": "all_chat_administrators"},
+            "commands": [
+                {"command": "buy", "description": "Buy an item"},
+                {"command": "ban", "description": "Ban a user"},
+            ],
+        })
+    );
+
+    let _ = shutdown.send(());
+}
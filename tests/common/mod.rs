@@ -0,0 +1,221 @@
+//! Mock Bot API and fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use telegram_botkit::router::AppCtx;
+
+use teloxide::Bot;
+use teloxide::types::{CallbackQuery, ChatId, Message};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+pub type Log = Arc<Mutex<Vec<String>>>;
+
+/// Response body for a call to `method`, the `n`th in `calls`.
+type Reply = Arc<dyn Fn(&str, usize) -> serde_json::Value + Send + Sync>;
+
+/// Methods answered with plain `true`.
+const BOOL_METHODS: &[&str] = &[
+    "AnswerCallbackQuery",
+    "AnswerInlineQuery",
+    "DeleteMessage",
+    "SetMyCommands",
+];
+
+/// Bot API stand-in recording every call.
+#[derive(Clone)]
+pub struct MockApi {
+    /// `"Method {json}"` per call.
+    pub bodies: Log,
+    /// Method names only; tests may log their own steps in between.
+    pub calls: Log,
+    reply: Reply,
+}
+
+impl Default for MockApi {
+    fn default() -> Self {
+        Self::replying(|method, _| {
+            if BOOL_METHODS.contains(&method) {
+                ok(serde_json::json!(true))
+            } else {
+                ok(sent_message(100))
+            }
+        })
+    }
+}
+
+impl MockApi {
+    pub fn replying(
+        reply: impl Fn(&str, usize) -> serde_json::Value + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            bodies: Log::default(),
+            calls: Log::default(),
+            reply: Arc::new(reply),
+        }
+    }
+
+    /// Serve until the sender fires; the bot talks to this server.
+    pub async fn start(&self) -> (Bot, oneshot::Sender<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, mut rx) = oneshot::channel::<()>();
+        let api = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut rx => break,
+                    res = listener.accept() => {
+                        let (stream, _) = res.unwrap();
+                        let io = TokioIo::new(stream);
+                        let api = api.clone();
+                        let svc = service_fn(move |req| api.clone().handle(req));
+                        let _ = http1::Builder::new().serve_connection(io, svc).await;
+                    }
+                }
+            }
+        });
+
+        let bot = Bot::with_client("TEST", reqwest::Client::new())
+            .set_api_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap());
+        (bot, tx)
+    }
+
+    async fn handle(
+        self,
+        req: Request<hyper::body::Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let method = req
+            .uri()
+            .path()
+            .rsplit('/')
+            .next()
+            .unwrap_or("")
+            .to_string();
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        self.bodies
+            .lock()
+            .unwrap()
+            .push(format!("{method} {}", String::from_utf8_lossy(&body)));
+        let n = {
+            let mut g = self.calls.lock().unwrap();
+            g.push(method.clone());
+            g.len()
+        };
+
+        let body = (self.reply)(&method, n);
+
+        Ok(Response::builder()
+            .status(200)
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from(serde_json::to_vec(&body).unwrap())))
+            .unwrap())
+    }
+}
+
+pub fn ok(result: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({"ok": true, "result": result})
+}
+
+/// Text message `id` in chat 1.
+pub fn sent_message(id: usize) -> serde_json::Value {
+    serde_json::json!({
+        "message_id": id,
+        "date": 0,
+        "chat": {"id": 1, "type": "private"},
+        "text": "ok"
+    })
+}
+
+pub fn take(log: &Log) -> Vec<String> {
+    std::mem::take(&mut *log.lock().unwrap())
+}
+
+pub struct TestAppCtx {
+    pub bot: Bot,
+    pub chat: ChatId,
+    pub user: i64,
+    /// Passes the test guards.
+    pub admin: bool,
+}
+
+impl TestAppCtx {
+    /// The chat's own user, as in private chats.
+    pub fn new(bot: Bot, chat: ChatId) -> Self {
+        Self {
+            bot,
+            chat,
+            user: chat.0,
+            admin: false,
+        }
+    }
+}
+
+impl AppCtx for TestAppCtx {
+    type Bot = Bot;
+
+    fn bot(&self) -> &Self::Bot {
+        &self.bot
+    }
+
+    fn chat(&self) -> ChatId {
+        self.chat
+    }
+
+    fn user_id(&self) -> i64 {
+        self.user
+    }
+
+    fn bot_username(&self) -> Option<&str> {
+        Some("botkit_bot")
+    }
+}
+
+fn chat_json(chat: ChatId) -> serde_json::Value {
+    if chat.0 > 0 {
+        serde_json::json!({"id": chat.0, "type": "private"})
+    } else {
+        serde_json::json!({"id": chat.0, "type": "supergroup", "title": "g"})
+    }
+}
+
+/// Press on menu message 100 in chat 1.
+pub fn callback(data: &str) -> CallbackQuery {
+    callback_from(1, ChatId(1), data)
+}
+
+pub fn callback_from(user: i64, chat: ChatId, data: &str) -> CallbackQuery {
+    serde_json::from_value(serde_json::json!({
+        "id": "1",
+        "from": {"id": user, "is_bot": false, "first_name": "u"},
+        "chat_instance": "ci",
+        "data": data,
+        "message": {
+            "message_id": 100,
+            "date": 0,
+            "chat": chat_json(chat),
+            "text": "menu"
+        }
+    }))
+    .unwrap()
+}
+
+pub fn text_message(text: &str) -> Message {
+    serde_json::from_value(serde_json::json!({
+        "message_id": 5,
+        "date": 0,
+        "chat": {"id": 1, "type": "private"},
+        "from": {"id": 1, "is_bot": false, "first_name": "u"},
+        "text": text,
+    }))
+    .unwrap()
+}
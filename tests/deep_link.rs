@@ -0,0 +1,163 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::deep_link::{START_PARAM_MAX, decode_json, start_link, start_param};
+use telegram_botkit::router::{AppEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::{SimpleSession, UiStore};
+use telegram_botkit::viewport::{Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::ChatId;
+
+use common::{MockApi, TestAppCtx, text_message};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct HomeScene;
+
+impl Scene for HomeScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "home";
+    const PREFIX: &'static str = "h";
+
+    type State = ();
+    type Event = ();
+
+    fn init(&self, _ctx: &Ctx) {}
+
+    fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+        View::text("home")
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+#[derive(Default)]
+struct ItemScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct Item {
+    id: u64,
+}
+
+impl Scene for ItemScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "item";
+    const PREFIX: &'static str = "item";
+
+    type State = Item;
+    type Event = ();
+
+    fn init(&self, _ctx: &Ctx) -> Item {
+        Item { id: 0 }
+    }
+
+    fn init_with(&self, ctx: &Ctx, params: &serde_json::Value) -> Item {
+        serde_json::from_value(params.clone()).unwrap_or_else(|_| self.init(ctx))
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &Item) -> View {
+        View::text(format!("item {}", s.id))
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+#[test]
+fn start_param_round_trips_within_limit() {
+    let param = start_param("item", &Item { id: 7 }).unwrap();
+    assert!(param.starts_with("item-"));
+    assert!(
+        param[5..]
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    );
+    assert_eq!(decode_json(&param[5..]), Some(serde_json::json!({"id": 7})));
+    assert_eq!(
+        start_link("@botkit_bot", "item", &Item { id: 7 }).unwrap(),
+        format!("https://t.me/botkit_bot?start={param}")
+    );
+
+    assert_eq!(start_param("it-em", &1), None);
+    assert_eq!(start_param("item", &"x".repeat(START_PARAM_MAX)), None);
+    assert_eq!(decode_json("not json!"), None);
+}
+
+#[tokio::test]
+async fn start_opens_home_or_deep_linked_scene() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<HomeScene>())
+        .route(Builder::scene::<ItemScene>().deep_link())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_home_scene(HomeScene::ID)
+        .build()
+        .unwrap();
+
+    let last_sent = || {
+        let bodies = bodies.lock().unwrap();
+        bodies
+            .iter()
+            .rev()
+            .find(|b| b.starts_with("SendMessage") || b.starts_with("EditMessageText"))
+            .cloned()
+            .unwrap()
+    };
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("/start")))
+        .await
+        .unwrap();
+    assert!(last_sent().contains("\"home\""));
+
+    let param = start_param("item", &Item { id: 7 }).unwrap();
+    let start = format!("/start {param}");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message(&start)))
+        .await
+        .unwrap();
+    assert!(last_sent().contains("\"item 7\""));
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("item"));
+
+    // Undecodable payloads land on the home scene
+    router
+        .handle(
+            &ctx,
+            &vp,
+            &d,
+            AppEvent::Msg(&text_message("/start@botkit_bot item-!!")),
+        )
+        .await
+        .unwrap();
+    assert!(last_sent().contains("\"home\""));
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("home"));
+
+    let _ = shutdown.send(());
+}
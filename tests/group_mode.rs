@@ -0,0 +1,226 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::core::restore_state;
+use telegram_botkit::router::{AppEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::{SimpleSession, UiStore, dialogue_key};
+use telegram_botkit::viewport::{MetaSpec, Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::{CallbackQuery, ChatId, MessageId};
+
+use common::{MockApi, TestAppCtx, callback_from};
+use std::sync::Arc;
+
+const GROUP: ChatId = ChatId(-100123);
+
+#[derive(Default)]
+struct CounterScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct State(u32);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Inc,
+}
+
+impl ActionCodec for Event {
+    fn encode(&self, prefix: &str) -> String {
+        format!("{prefix}:inc")
+    }
+
+    fn decode(prefix: &str, s: &str) -> Option<Self> {
+        (s.strip_prefix(prefix)? == ":inc").then_some(Event::Inc)
+    }
+}
+
+impl Scene for CounterScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "counter";
+    const PREFIX: &'static str = "c";
+
+    type State = State;
+    type Event = Event;
+
+    fn init(&self, _ctx: &Ctx) -> State {
+        State(0)
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &State) -> View {
+        View::text(s.0.to_string())
+    }
+
+    fn update(&self, _ctx: &Ctx, s: &State, e: Event) -> Effect<State, Event> {
+        match e {
+            Event::Inc => Effect::Stay(State(s.0 + 1), RenderPolicy::EditOrReply),
+        }
+    }
+}
+
+fn callback(from: i64) -> CallbackQuery {
+    callback_from(from, GROUP, "c:inc")
+}
+
+#[test]
+fn dialogue_key_splits_group_members() {
+    assert_eq!(dialogue_key(ChatId(5), 5), ChatId(5));
+
+    let a = dialogue_key(GROUP, 1);
+    assert_eq!(a, dialogue_key(GROUP, 1));
+    assert_ne!(a, dialogue_key(GROUP, 2));
+    assert_ne!(a, dialogue_key(ChatId(-100124), 1));
+    assert!(a.0 < -(1 << 60));
+}
+
+#[tokio::test]
+async fn foreign_menu_callback_is_rejected() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let owner_d = Dialogue::new(storage.clone(), dialogue_key(GROUP, 1));
+    let other_d = Dialogue::new(storage, dialogue_key(GROUP, 2));
+    let sctx = Ctx {
+        user_id: 1,
+        ..Default::default()
+    };
+
+    vp.save_meta_public(
+        GROUP,
+        100,
+        MetaSpec {
+            state_json: Some(serde_json::to_string(&State(0)).unwrap()),
+            ..MetaSpec::new(CounterScene::ID, CounterScene::VERSION).with_owner(1)
+        },
+    )
+    .await
+    .unwrap();
+
+    let routes = Builder::new()
+        .route(Builder::scene::<CounterScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_group_mode(true)
+        .build()
+        .unwrap();
+
+    let other = TestAppCtx {
+        user: 2,
+        ..TestAppCtx::new(bot.clone(), GROUP)
+    };
+    router
+        .handle(&other, &vp, &other_d, AppEvent::Cb(&callback(2)))
+        .await
+        .unwrap();
+    {
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].starts_with("AnswerCallbackQuery"));
+        assert!(bodies[0].contains("belongs to another user"));
+    }
+
+    let meta = Some((GROUP, MessageId(100)));
+    let (st, _) = restore_state(&CounterScene, &vp, &owner_d, &sctx, meta).await;
+    assert_eq!(st, State(0));
+
+    let owner = TestAppCtx {
+        user: 1,
+        ..TestAppCtx::new(bot, GROUP)
+    };
+    router
+        .handle(&owner, &vp, &owner_d, AppEvent::Cb(&callback(1)))
+        .await
+        .unwrap();
+
+    let (st, _) = restore_state(&CounterScene, &vp, &owner_d, &sctx, meta).await;
+    assert_eq!(st, State(1));
+    let s = other_d.get_or_default().await.unwrap();
+    assert_eq!(s.ui_get_active_scene_id(), None);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn activate_from_callback_skips_foreign_menus() {
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, dialogue_key(GROUP, 2));
+    let routes =
+        Builder::<TestAppCtx, SimpleSession, InMemStorage<SimpleSession>, MemoryStore>::new()
+            .route(Builder::scene::<CounterScene>().codec())
+            .build()
+            .unwrap();
+
+    vp.save_meta_public(
+        GROUP,
+        100,
+        MetaSpec::new(CounterScene::ID, CounterScene::VERSION).with_owner(1),
+    )
+    .await
+    .unwrap();
+
+    vp.activate_from_callback(&d, &callback(2), &routes, true)
+        .await;
+    assert!(d.get().await.unwrap().is_none());
+
+    vp.activate_from_callback(&d, &callback(1), &routes, true)
+        .await;
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("counter"));
+    assert_eq!(s.ui_get_last_action_message_id(), Some(100));
+}
+
+#[tokio::test]
+async fn shared_group_menu_can_be_pressed_by_anyone_without_group_mode() {
+    let (bot, shutdown) = MockApi::default().start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, GROUP);
+    let sctx = Ctx {
+        user_id: 1,
+        ..Default::default()
+    };
+
+    // Rendered for user 1, who has moved on to a newer menu
+    vp.save_meta_public(
+        GROUP,
+        100,
+        MetaSpec {
+            state_json: Some(serde_json::to_string(&State(0)).unwrap()),
+            ..MetaSpec::new(CounterScene::ID, CounterScene::VERSION).with_owner(1)
+        },
+    )
+    .await
+    .unwrap();
+    let mut s = d.get_or_default().await.unwrap();
+    s.ui_set_last_action_message_id(Some(200));
+    d.update(s).await.unwrap();
+
+    let routes = Builder::new()
+        .route(Builder::scene::<CounterScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+
+    let other = TestAppCtx {
+        user: 2,
+        ..TestAppCtx::new(bot, GROUP)
+    };
+    router
+        .handle(&other, &vp, &d, AppEvent::Cb(&callback(2)))
+        .await
+        .unwrap();
+
+    let s = d.get_or_default().await.unwrap();
+    assert_eq!(s.ui_get_last_action_message_id(), Some(100));
+    let meta = Some((GROUP, MessageId(100)));
+    let (st, _) = restore_state(&CounterScene, &vp, &d, &sctx, meta).await;
+    assert_eq!(st, State(1));
+
+    let _ = shutdown.send(());
+}
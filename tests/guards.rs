@@ -0,0 +1,310 @@
+mod common;
+
+use telegram_botkit::router::compose::{Builder, Denied, RouterDispatch, Switched};
+use telegram_botkit::router::{AppEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::{NavEntry, SimpleSession, UiStore};
+use telegram_botkit::ui::callback::BACK;
+use telegram_botkit::viewport::{Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::ChatId;
+
+use common::{Log, MockApi, TestAppCtx, callback, text_message};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+type Storage = InMemStorage<SimpleSession>;
+
+#[derive(Default)]
+struct HomeScene;
+
+impl Scene for HomeScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "home";
+    const PREFIX: &'static str = "h";
+
+    type State = ();
+    type Event = ();
+
+    fn init(&self, _ctx: &Ctx) {}
+
+    fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+        View::text("home")
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Push((), SceneSwitch::to(AdminScene::ID))
+    }
+
+    fn bindings(&self) -> Bindings<()> {
+        Bindings {
+            msg: vec![],
+            cb: vec![CbBinding::new(CbKey::Exact("h:admin"), |_| Some(()))],
+        }
+    }
+}
+
+#[derive(Default)]
+struct AdminScene;
+
+impl Scene for AdminScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "admin";
+    const PREFIX: &'static str = "adm";
+
+    type State = ();
+    type Event = ();
+
+    fn init(&self, _ctx: &Ctx) {}
+
+    fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+        View::text("admin panel")
+    }
+
+    fn update(&self, _ctx: &Ctx, _s: &(), _e: ()) -> Effect<(), ()> {
+        Effect::Stay((), RenderPolicy::EditOrReply)
+    }
+
+    fn bindings(&self) -> Bindings<()> {
+        Bindings {
+            msg: vec![MsgBinding::new(MsgPattern::Command("ban"), |_, _| Some(()))],
+            cb: vec![CbBinding::new(CbKey::Exact("adm:refresh"), |_| Some(()))],
+        }
+    }
+}
+
+fn rendered(bodies: &Log, text: &str) -> bool {
+    bodies.lock().unwrap().iter().any(|b| {
+        (b.starts_with("SendMessage") || b.starts_with("EditMessageText"))
+            && b.contains(&format!("\"{text}\""))
+    })
+}
+
+fn builder(denied: Denied) -> Builder<TestAppCtx, SimpleSession, Storage, MemoryStore> {
+    Builder::new().route(Builder::scene::<HomeScene>()).route(
+        Builder::scene::<AdminScene>()
+            .guard(|ctx: &TestAppCtx| Box::pin(async move { ctx.admin }))
+            .on_denied(denied),
+    )
+}
+
+#[tokio::test]
+async fn denied_callback_and_command_get_alert() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let router = RouterBuilder::new()
+        .with_routes(builder(Denied::default()).build().unwrap())
+        .build()
+        .unwrap();
+
+    let q = callback("adm:refresh");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&q))
+        .await
+        .unwrap();
+    {
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].starts_with("AnswerCallbackQuery"));
+        assert!(bodies[0].contains("You don't have access"));
+        assert!(bodies[0].contains("\"show_alert\":true"));
+    }
+
+    // Commands of a denied scene are not routed to it
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("/ban")))
+        .await
+        .unwrap();
+    assert!(!rendered(&bodies, "admin panel"));
+
+    // The crafted callback never made the scene active
+    let s = d.get().await.unwrap().unwrap_or_default();
+    assert_ne!(s.ui_get_active_scene_id().as_deref(), Some("admin"));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn denied_push_leaves_no_nav_entry() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let router = RouterBuilder::new()
+        .with_routes(builder(Denied::default()).build().unwrap())
+        .build()
+        .unwrap();
+
+    let q = callback("h:admin");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&q))
+        .await
+        .unwrap();
+
+    assert!(!rendered(&bodies, "admin panel"));
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_nav_depth(), 0);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn back_into_revoked_scene_is_denied() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let mut s = SimpleSession::default();
+    s.ui_push_nav_entry(NavEntry {
+        scene_id: AdminScene::ID.to_string(),
+        scene_version: AdminScene::VERSION,
+        state_json: None,
+    });
+    d.update(s).await.unwrap();
+
+    let router = RouterBuilder::new()
+        .with_routes(builder(Denied::default()).build().unwrap())
+        .build()
+        .unwrap();
+
+    let q = callback(BACK);
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&q))
+        .await
+        .unwrap();
+
+    assert!(!rendered(&bodies, "admin panel"));
+    assert!(
+        bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|b| b.contains("You don't have access"))
+    );
+    let s = d.get().await.unwrap().unwrap();
+    assert_ne!(s.ui_get_active_scene_id().as_deref(), Some("admin"));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn denied_switch_redirects() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = builder(Denied::Redirect(HomeScene::ID)).build().unwrap();
+
+    assert_eq!(
+        routes
+            .switch_to_scene_by_id(AdminScene::ID, &ctx, &vp, &d)
+            .await
+            .unwrap(),
+        Switched::Denied
+    );
+    assert!(rendered(&bodies, "home"));
+    assert!(!rendered(&bodies, "admin panel"));
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("home"));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn allowed_user_enters_guarded_scene() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx {
+        admin: true,
+        ..TestAppCtx::new(bot, ChatId(1))
+    };
+
+    let routes = builder(Denied::Redirect(HomeScene::ID)).build().unwrap();
+
+    assert_eq!(
+        routes
+            .switch_to_scene_by_id(AdminScene::ID, &ctx, &vp, &d)
+            .await
+            .unwrap(),
+        Switched::Entered
+    );
+    assert!(rendered(&bodies, "admin panel"));
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("admin"));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn guard_runs_once_and_only_for_matching_updates() {
+    let (bot, shutdown) = MockApi::default().start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let ctx = TestAppCtx {
+        admin: true,
+        ..TestAppCtx::new(bot, ChatId(1))
+    };
+
+    let checks = Arc::new(AtomicUsize::new(0));
+    let routes = || {
+        let checks = checks.clone();
+        Builder::new()
+            .route(Builder::scene::<HomeScene>())
+            .route(
+                Builder::scene::<AdminScene>().guard(move |ctx: &TestAppCtx| {
+                    checks.fetch_add(1, Ordering::SeqCst);
+                    Box::pin(async move { ctx.admin })
+                }),
+            )
+            .build()
+            .unwrap()
+    };
+    let router = RouterBuilder::new().with_routes(routes()).build().unwrap();
+
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&callback("adm:refresh")))
+        .await
+        .unwrap();
+    assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+    // Without an active scene only a matching binding asks the guard
+    let routes = routes();
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let hello = text_message("hello");
+    assert!(
+        !routes
+            .handle_msg(None, &ctx, &vp, &d, &hello)
+            .await
+            .unwrap()
+    );
+    assert_eq!(checks.load(Ordering::SeqCst), 1);
+
+    let ban = text_message("/ban");
+    assert!(routes.handle_msg(None, &ctx, &vp, &d, &ban).await.unwrap());
+    assert_eq!(checks.load(Ordering::SeqCst), 2);
+
+    let _ = shutdown.send(());
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::core::restore_inline_state;
+use telegram_botkit::router::{AppEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::SimpleSession;
+use telegram_botkit::ui::callback::INLINE_RESULT;
+use telegram_botkit::viewport::{Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::{
+    CallbackQuery, ChatId, ChosenInlineResult, InlineKeyboardButton, InlineKeyboardMarkup,
+    InlineQuery,
+};
+
+use common::{MockApi, TestAppCtx, ok, take};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct CounterScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct Count(u32);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Inc,
+}
+
+impl ActionCodec for Event {
+    fn encode(&self, prefix: &str) -> String {
+        match self {
+            Event::Inc => format!("{prefix}:inc"),
+        }
+    }
+
+    fn decode(prefix: &str, s: &str) -> Option<Self> {
+        match s.strip_prefix(prefix)? {
+            ":inc" => Some(Event::Inc),
+            _ => None,
+        }
+    }
+}
+
+impl Scene for CounterScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "counter";
+    const PREFIX: &'static str = "c";
+
+    type State = Count;
+    type Event = Event;
+
+    fn init(&self, _ctx: &Ctx) -> Count {
+        Count(0)
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &Count) -> View {
+        let inc = Event::Inc.encode(Self::PREFIX);
+
+        View::text(format!("Count: {}", s.0)).with_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::callback("+1", inc),
+        ]]))
+    }
+
+    fn update(&self, _ctx: &Ctx, s: &Count, e: Event) -> Effect<Count, Event> {
+        match e {
+            Event::Inc => Effect::Stay(Count(s.0 + 1), RenderPolicy::EditOrReply),
+        }
+    }
+
+    fn inline_query(&self, _ctx: &Ctx, query: &str) -> Option<Vec<InlineResult<Count>>> {
+        let start = query.trim().parse().ok()?;
+
+        Some(vec![InlineResult {
+            title: format!("Counter from {start}"),
+            description: None,
+            state: Count(start),
+        }])
+    }
+}
+
+fn from() -> serde_json::Value {
+    serde_json::json!({"id": 1, "is_bot": false, "first_name": "u"})
+}
+
+fn inline_query(query: &str) -> InlineQuery {
+    serde_json::from_value(serde_json::json!({
+        "id": "iq",
+        "from": from(),
+        "query": query,
+        "offset": "",
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn inline_results_stay_interactive() {
+    // Inline edits answer `true`, not a message
+    let api = MockApi::replying(|_, _| ok(serde_json::json!(true)));
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<CounterScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::InlineQuery(&inline_query("5")))
+        .await
+        .unwrap();
+    let sent = take(&bodies);
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].starts_with("AnswerInlineQuery"));
+    assert!(sent[0].contains("Count: 5"));
+    assert!(sent[0].contains("c:inc"));
+
+    let start = sent[0].find(INLINE_RESULT).unwrap();
+    let end = start + sent[0][start..].find('"').unwrap();
+    let result_id = sent[0][start..end].to_string();
+
+    let chosen: ChosenInlineResult = serde_json::from_value(serde_json::json!({
+        "result_id": result_id,
+        "from": from(),
+        "query": "5",
+        "inline_message_id": "imid",
+    }))
+    .unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::ChosenInlineResult(&chosen))
+        .await
+        .unwrap();
+    let meta = vp.load_inline_meta("imid").await.unwrap().unwrap();
+    assert_eq!(meta.scene_id, CounterScene::ID);
+
+    let q: CallbackQuery = serde_json::from_value(serde_json::json!({
+        "id": "1",
+        "from": from(),
+        "chat_instance": "ci",
+        "data": "c:inc",
+        "inline_message_id": "imid",
+    }))
+    .unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&q))
+        .await
+        .unwrap();
+
+    let sent = take(&bodies);
+    assert!(sent[0].starts_with("AnswerCallbackQuery"));
+    assert!(sent[1].starts_with("EditMessageText"));
+    assert!(sent[1].contains("\"inline_message_id\":\"imid\""));
+    assert!(sent[1].contains("Count: 6"));
+
+    let sctx = Ctx {
+        user_id: 1,
+        ..Default::default()
+    };
+    let st = restore_inline_state(&CounterScene, &vp, "imid", &sctx).await;
+    assert_eq!(st, Count(6));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn unanswered_inline_query_gets_empty_results() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<CounterScene>().codec())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::InlineQuery(&inline_query("abc")))
+        .await
+        .unwrap();
+    let sent = take(&bodies);
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].starts_with("AnswerInlineQuery"));
+    assert!(sent[0].contains("\"results\":[]"));
+
+    let _ = shutdown.send(());
+}
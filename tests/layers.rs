@@ -0,0 +1,163 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::layer::{Layer, Next};
+use telegram_botkit::router::{AppEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::{SimpleSession, UiStore};
+use telegram_botkit::viewport::{Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::ChatId;
+
+use common::{Log, MockApi, TestAppCtx, text_message};
+
+type Storage = InMemStorage<SimpleSession>;
+
+#[derive(Default)]
+struct HomeScene;
+
+impl Scene for HomeScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "home";
+    const PREFIX: &'static str = "h";
+
+    type State = ();
+    type Event = ();
+
+    fn init(&self, _ctx: &Ctx) {}
+
+    fn render(&self, _ctx: &Ctx, _s: &()) -> View {
+        View::text("home")
+    }
+
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        _s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
+        Effect::Noop
+    }
+}
+
+struct Audit {
+    name: &'static str,
+    log: Log,
+}
+
+#[async_trait::async_trait]
+impl Layer<TestAppCtx, SimpleSession, Storage> for Audit {
+    async fn handle<'a>(
+        &'a self,
+        _ctx: &'a TestAppCtx,
+        _d: &'a Dialogue<SimpleSession, Storage>,
+        _ev: &'a AppEvent<'a>,
+        next: Next<'a>,
+    ) -> anyhow::Result<()> {
+        self.log.lock().unwrap().push(format!("{} in", self.name));
+        let res = next.run().await;
+        self.log.lock().unwrap().push(format!("{} out", self.name));
+
+        res
+    }
+}
+
+// Only admins get through; everyone else is parked in "maintenance"
+struct Maintenance;
+
+#[async_trait::async_trait]
+impl Layer<TestAppCtx, SimpleSession, Storage> for Maintenance {
+    async fn handle<'a>(
+        &'a self,
+        ctx: &'a TestAppCtx,
+        d: &'a Dialogue<SimpleSession, Storage>,
+        ev: &'a AppEvent<'a>,
+        next: Next<'a>,
+    ) -> anyhow::Result<()> {
+        if ctx.admin || !matches!(ev, AppEvent::Msg(_)) {
+            return next.run().await;
+        }
+
+        let mut s = d.get_or_default().await?;
+        s.ui_set_active_scene_id(Some("maintenance".into()));
+        d.update(s).await?;
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn layers_wrap_dispatch_in_order() {
+    let api = MockApi::default();
+    let log = api.calls.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx {
+        admin: true,
+        ..TestAppCtx::new(bot, ChatId(1))
+    };
+
+    let routes = Builder::new()
+        .route(Builder::scene::<HomeScene>())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_home_scene(HomeScene::ID)
+        .layer(Audit {
+            name: "a",
+            log: log.clone(),
+        })
+        .layer(Maintenance)
+        .layer(Audit {
+            name: "b",
+            log: log.clone(),
+        })
+        .build()
+        .unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("/start")))
+        .await
+        .unwrap();
+    assert_eq!(
+        *log.lock().unwrap(),
+        ["a in", "b in", "SendMessage", "b out", "a out"]
+    );
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn layer_can_short_circuit_and_edit_session() {
+    let api = MockApi::default();
+    let log = api.calls.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let d = Dialogue::new(Storage::new(), ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<HomeScene>())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_home_scene(HomeScene::ID)
+        .layer(Maintenance)
+        .build()
+        .unwrap();
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Msg(&text_message("/start")))
+        .await
+        .unwrap();
+    assert!(log.lock().unwrap().is_empty());
+
+    let s = d.get().await.unwrap().unwrap();
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("maintenance"));
+
+    let _ = shutdown.send(());
+}
@@ -0,0 +1,259 @@
+mod common;
+
+use telegram_botkit::router::compose::Builder;
+use telegram_botkit::router::{AppEvent, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::SimpleSession;
+use telegram_botkit::viewport::Viewport;
+use telegram_botkit::viewport::store::{MemoryStore, NoopStore, RateLimit, Store};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::ChatId;
+
+use common::{Log, MockApi, TestAppCtx, callback};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+struct VoteScene;
+
+#[derive(Clone, Debug)]
+enum Event {
+    Vote,
+    Refresh,
+}
+
+impl Scene for VoteScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "vote";
+    const PREFIX: &'static str = "v";
+
+    type State = u32;
+    type Event = Event;
+
+    fn init(&self, _ctx: &Ctx) -> u32 {
+        0
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &u32) -> View {
+        View::text(format!("votes: {s}"))
+    }
+
+    fn update(&self, _ctx: &Ctx, s: &u32, e: Event) -> Effect<u32, Event> {
+        let n = match e {
+            Event::Vote => s + 1,
+            Event::Refresh => *s,
+        };
+
+        Effect::Stay(n, RenderPolicy::EditOrReply)
+    }
+
+    fn bindings(&self) -> Bindings<Event> {
+        Bindings {
+            msg: vec![],
+            cb: vec![
+                CbBinding::new(CbKey::Exact("v:vote"), |_| Some(Event::Vote))
+                    .with_cooldown(Duration::from_secs(60)),
+                CbBinding::new(CbKey::Exact("v:refresh"), |_| Some(Event::Refresh)),
+                // Never reached for the two above
+                CbBinding::new(CbKey::Prefix("v:"), |_| Some(Event::Refresh))
+                    .with_cooldown(Duration::from_secs(60)),
+            ],
+        }
+    }
+}
+
+fn throttled(bodies: &Log) -> usize {
+    bodies
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|b| b.starts_with("AnswerCallbackQuery") && b.contains("slow down"))
+        .count()
+}
+
+#[tokio::test]
+async fn memory_bucket_allows_burst_then_refills() {
+    let store = MemoryStore::default();
+    let limit = RateLimit::new(2, Duration::from_secs(1));
+
+    assert_eq!(store.take_token("u:1", limit).await.unwrap(), Some(true));
+    assert_eq!(store.take_token("u:1", limit).await.unwrap(), Some(true));
+    assert_eq!(store.take_token("u:1", limit).await.unwrap(), Some(false));
+    // Buckets are independent
+    assert_eq!(store.take_token("u:2", limit).await.unwrap(), Some(true));
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(store.take_token("u:1", limit).await.unwrap(), Some(true));
+    assert_eq!(store.take_token("u:1", limit).await.unwrap(), Some(false));
+}
+
+#[tokio::test]
+async fn stores_without_buckets_limit_in_process() {
+    let vp = Viewport::new(NoopStore);
+    let limit = RateLimit::new(1, Duration::from_secs(60));
+
+    assert!(vp.take_token("u:1", limit).await);
+    assert!(!vp.take_token("u:1", limit).await);
+}
+
+#[tokio::test]
+async fn user_limit_throttles_callbacks_with_toast() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<VoteScene>())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_user_limit(RateLimit::new(1, Duration::from_secs(60)))
+        .build()
+        .unwrap();
+
+    let q = callback("v:refresh");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&q))
+        .await
+        .unwrap();
+    assert_eq!(throttled(&bodies), 0);
+
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&q))
+        .await
+        .unwrap();
+    assert_eq!(throttled(&bodies), 1);
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn cooldown_applies_to_its_binding_only() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(Builder::scene::<VoteScene>())
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new()
+        .with_routes(routes)
+        .with_throttle_text("Hold on, slow down")
+        .build()
+        .unwrap();
+
+    let vote = callback("v:vote");
+    let refresh = callback("v:refresh");
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&vote))
+        .await
+        .unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&vote))
+        .await
+        .unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&refresh))
+        .await
+        .unwrap();
+    router
+        .handle(&ctx, &vp, &d, AppEvent::Cb(&refresh))
+        .await
+        .unwrap();
+
+    assert_eq!(throttled(&bodies), 1);
+    assert!(
+        bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|b| b.contains("Hold on, slow down"))
+    );
+
+    let _ = shutdown.send(());
+}
+
+#[derive(Default)]
+struct CodecVoteScene;
+
+impl ActionCodec for Event {
+    fn encode(&self, prefix: &str) -> String {
+        match self {
+            Event::Vote => format!("{prefix}:vote"),
+            Event::Refresh => format!("{prefix}:refresh"),
+        }
+    }
+
+    fn decode(prefix: &str, s: &str) -> Option<Self> {
+        match s.strip_prefix(prefix)? {
+            ":vote" => Some(Event::Vote),
+            ":refresh" => Some(Event::Refresh),
+            _ => None,
+        }
+    }
+}
+
+impl Scene for CodecVoteScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "codec_vote";
+    const PREFIX: &'static str = "cv";
+
+    type State = u32;
+    type Event = Event;
+
+    fn init(&self, ctx: &Ctx) -> u32 {
+        VoteScene.init(ctx)
+    }
+
+    fn render(&self, ctx: &Ctx, s: &u32) -> View {
+        VoteScene.render(ctx, s)
+    }
+
+    fn update(&self, ctx: &Ctx, s: &u32, e: Event) -> Effect<u32, Event> {
+        VoteScene.update(ctx, s, e)
+    }
+}
+
+#[tokio::test]
+async fn codec_cooldown_throttles_decoded_callbacks() {
+    let api = MockApi::default();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let routes = Builder::new()
+        .route(
+            Builder::scene::<CodecVoteScene>()
+                .codec()
+                .codec_cooldown(Duration::from_secs(60)),
+        )
+        .build()
+        .unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+
+    for data in ["cv:vote", "cv:refresh", "cv:bogus"] {
+        router
+            .handle(&ctx, &vp, &d, AppEvent::Cb(&callback(data)))
+            .await
+            .unwrap();
+    }
+
+    // Shared by the codec's events; undecodable data isn't counted
+    assert_eq!(throttled(&bodies), 1);
+
+    let _ = shutdown.send(());
+}
@@ -53,10 +53,16 @@ impl Scene for TestScene {
             markup: None,
             parse_mode: None,
             disable_web_page_preview: None,
+            media: None,
         }
     }
 
-    fn update(&self, _ctx: &Ctx, s: &Self::State, _e: Self::Event) -> Effect<Self::State> {
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
         Effect::Stay(s.clone(), RenderPolicy::EditOnly)
     }
 
@@ -66,10 +72,24 @@ impl Scene for TestScene {
             cb: vec![],
         }
     }
+
+    fn migrate(&self, from_version: u16, state: serde_json::Value) -> Option<Self::State> {
+        match from_version {
+            // v0 stored a bare counter object
+            0 => {
+                let n = state.get("legacy")?.as_u64()?;
+                Some(State::FromMeta(n as u32))
+            }
+            _ => None,
+        }
+    }
 }
 
 fn sctx() -> Ctx {
-    Ctx { user_id: 1 }
+    Ctx {
+        user_id: 1,
+        ..Default::default()
+    }
 }
 
 fn dialogue() -> Dialogue<SimpleSession, InMemStorage<SimpleSession>> {
@@ -134,6 +154,7 @@ async fn restore_from_meta_snapshot() {
             state_json: Some(json),
             state_ref: None,
             ttl_secs: 60,
+            owner_id: None,
         },
     )
     .await
@@ -170,6 +191,8 @@ async fn restore_meta_mismatch_falls_back_to_init() {
         state_checksum: Some("badhash".into()),
         created_at: 0,
         ttl_secs: 60,
+        owner_id: None,
+        media: None,
     };
     store.save(ChatId(1), mid, meta).await.unwrap();
 
@@ -207,6 +230,7 @@ async fn restore_dialogue_checksum_mismatch_falls_back_to_meta() {
             state_json: Some(meta_json),
             state_ref: None,
             ttl_secs: 60,
+            owner_id: None,
         },
     )
     .await
@@ -240,3 +264,105 @@ async fn restore_dialogue_checksum_mismatch_falls_back_to_meta() {
     assert_eq!(st, State::FromMeta(42));
     assert_eq!(label, "meta");
 }
+
+#[tokio::test]
+async fn restore_migrates_old_meta_snapshot() {
+    let store = MapStore::default();
+    let vp = Viewport::new(store.clone());
+    let d = dialogue();
+
+    let mid = 300;
+    vp.save_meta_public(
+        ChatId(1),
+        mid,
+        MetaSpec {
+            scene_id: TestScene::ID,
+            scene_version: 0,
+            state_json: Some(r#"{"legacy":3}"#.into()),
+            state_ref: None,
+            ttl_secs: 60,
+            owner_id: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    let (st, label) = restore_state(
+        &TestScene,
+        &vp,
+        &d,
+        &sctx(),
+        Some((ChatId(1), MessageId(mid))),
+    )
+    .await;
+
+    assert_eq!(st, State::FromMeta(3));
+    assert_eq!(label, "migrated");
+}
+
+#[tokio::test]
+async fn restore_migrates_old_dialogue_snapshot() {
+    let d = dialogue();
+    let vp = Viewport::new(MapStore::default());
+
+    let mid = 301;
+    let json = r#"{"legacy":4}"#;
+    let env = serde_json::json!({
+        "_tgk": "tgk:s1",
+        "state": json,
+        "checksum": telegram_botkit::viewport::blake3_hex(json.as_bytes()),
+        "version": 0,
+    });
+
+    let mut sess = d.get_or_default().await.unwrap();
+    sess.ui_set_last_action_message_id(Some(mid));
+    sess.ui_set_scene_for_message(mid, env.to_string());
+    d.update(sess).await.unwrap();
+
+    let (st, label) = restore_state(
+        &TestScene,
+        &vp,
+        &d,
+        &sctx(),
+        Some((ChatId(1), MessageId(mid))),
+    )
+    .await;
+
+    assert_eq!(st, State::FromMeta(4));
+    assert_eq!(label, "migrated");
+}
+
+#[tokio::test]
+async fn restore_unknown_version_falls_back_to_init() {
+    let store = MapStore::default();
+    let vp = Viewport::new(store.clone());
+    let d = dialogue();
+
+    let mid = 302;
+    vp.save_meta_public(
+        ChatId(1),
+        mid,
+        MetaSpec {
+            scene_id: TestScene::ID,
+            scene_version: 7,
+            state_json: Some(r#"{"legacy":3}"#.into()),
+            state_ref: None,
+            ttl_secs: 60,
+            owner_id: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    let (st, label) = restore_state(
+        &TestScene,
+        &vp,
+        &d,
+        &sctx(),
+        Some((ChatId(1), MessageId(mid))),
+    )
+    .await;
+
+    assert_eq!(st, State::Root);
+    assert_eq!(label, "mismatch");
+}
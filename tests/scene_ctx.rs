@@ -0,0 +1,101 @@
+use telegram_botkit::router::AppCtx;
+use telegram_botkit::router::core::scene_ctx;
+use telegram_botkit::scene::ChatKind;
+
+use teloxide::Bot;
+use teloxide::types::{CallbackQuery, ChatId, Message};
+
+struct TestAppCtx {
+    bot: Bot,
+}
+
+impl AppCtx for TestAppCtx {
+    type Bot = Bot;
+
+    fn bot(&self) -> &Self::Bot {
+        &self.bot
+    }
+
+    fn chat(&self) -> ChatId {
+        ChatId(1)
+    }
+
+    fn user_id(&self) -> i64 {
+        1
+    }
+
+    fn language_code(&self) -> Option<&str> {
+        Some("en")
+    }
+}
+
+fn app_ctx() -> TestAppCtx {
+    TestAppCtx {
+        bot: Bot::new("TEST"),
+    }
+}
+
+#[test]
+fn scene_ctx_from_group_message() {
+    let m: Message = serde_json::from_value(serde_json::json!({
+        "message_id": 5,
+        "date": 0,
+        "chat": {"id": -100, "type": "group", "title": "team"},
+        "from": {
+            "id": 1,
+            "is_bot": false,
+            "first_name": "u",
+            "username": "alice",
+            "language_code": "de"
+        },
+        "text": "hi",
+    }))
+    .unwrap();
+
+    let ctx = scene_ctx(&app_ctx(), m.from.as_ref(), Some(&m.chat));
+
+    assert_eq!(ctx.user_id, 1);
+    assert_eq!(ctx.chat_id, -100);
+    assert_eq!(ctx.chat_kind, ChatKind::Group);
+    assert_eq!(ctx.language_code.as_deref(), Some("de"));
+    assert_eq!(ctx.username.as_deref(), Some("alice"));
+    assert!(ctx.now > 0);
+}
+
+#[test]
+fn scene_ctx_from_callback_in_supergroup() {
+    let q: CallbackQuery = serde_json::from_value(serde_json::json!({
+        "id": "1",
+        "from": {"id": 1, "is_bot": false, "first_name": "u"},
+        "chat_instance": "ci",
+        "data": "x",
+        "message": {
+            "message_id": 7,
+            "date": 0,
+            "chat": {"id": -1001, "type": "supergroup", "title": "team"},
+            "text": "menu"
+        }
+    }))
+    .unwrap();
+
+    let ctx = scene_ctx(
+        &app_ctx(),
+        Some(&q.from),
+        q.message.as_ref().map(|m| m.chat()),
+    );
+
+    assert_eq!(ctx.chat_id, -1001);
+    assert_eq!(ctx.chat_kind, ChatKind::Supergroup);
+    // Falls back to what the app reports
+    assert_eq!(ctx.language_code.as_deref(), Some("en"));
+    assert_eq!(ctx.username, None);
+}
+
+#[test]
+fn scene_ctx_without_update_uses_app_ctx() {
+    let ctx = scene_ctx(&app_ctx(), None, None);
+
+    assert_eq!(ctx.chat_id, 1);
+    assert_eq!(ctx.chat_kind, ChatKind::Private);
+    assert_eq!(ctx.language_code.as_deref(), Some("en"));
+}
@@ -25,10 +25,16 @@ impl Scene for Dummy {
             markup: None,
             parse_mode: None,
             disable_web_page_preview: None,
+            media: None,
         }
     }
 
-    fn update(&self, _ctx: &Ctx, s: &Self::State, _e: Self::Event) -> Effect<Self::State> {
+    fn update(
+        &self,
+        _ctx: &Ctx,
+        s: &Self::State,
+        _e: Self::Event,
+    ) -> Effect<Self::State, Self::Event> {
         Effect::Stay(s.clone(), RenderPolicy::EditOnly)
     }
 
@@ -85,3 +91,28 @@ fn snapshot_restore_checksum_mismatch() {
 
     assert!(d.restore(snap).is_none());
 }
+
+#[test]
+fn snapshot_restore_old_version_without_migration() {
+    let d = Dummy;
+    let s = DummyState { v: 7 };
+    let (json, _) = d.snapshot(&s);
+    let snap = Snapshot {
+        scene_version: Dummy::VERSION - 1,
+        scene_id: Dummy::ID,
+        state_json: json.as_deref(),
+        state_checksum: None,
+    };
+
+    // Unchanged state survives a VERSION bump
+    assert_eq!(d.restore(snap), Some(s));
+
+    let snap = Snapshot {
+        scene_version: Dummy::VERSION - 1,
+        scene_id: Dummy::ID,
+        state_json: Some(r#"{"count":7}"#),
+        state_checksum: None,
+    };
+
+    assert!(d.restore(snap).is_none());
+}
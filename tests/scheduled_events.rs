@@ -0,0 +1,256 @@
+mod common;
+
+use telegram_botkit::router::compose::{Builder, RouterDispatch};
+use telegram_botkit::router::core::{apply_effect, restore_state};
+use telegram_botkit::router::{AppCtx, RouterBuilder};
+use telegram_botkit::scene::*;
+use telegram_botkit::session::{SimpleSession, UiStore};
+use telegram_botkit::viewport::{Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::{ChatId, InlineKeyboardButtonKind, MessageId};
+
+use common::{MockApi, TestAppCtx, callback};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+struct QuoteScene;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum State {
+    Idle,
+    Quote(u32),
+    Expired,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Expire,
+}
+
+impl ActionCodec for Event {
+    fn encode(&self, prefix: &str) -> String {
+        match self {
+            Event::Expire => format!("{prefix}:expire"),
+        }
+    }
+
+    fn decode(prefix: &str, s: &str) -> Option<Self> {
+        match s.strip_prefix(prefix)? {
+            ":expire" => Some(Event::Expire),
+            _ => None,
+        }
+    }
+}
+
+impl Scene for QuoteScene {
+    const VERSION: u16 = 1;
+    const ID: &'static str = "quote";
+    const PREFIX: &'static str = "q";
+
+    type State = State;
+    type Event = Event;
+
+    fn init(&self, _ctx: &Ctx) -> State {
+        State::Idle
+    }
+
+    fn render(&self, _ctx: &Ctx, s: &State) -> View {
+        View::text(format!("{s:?}"))
+    }
+
+    fn update(&self, _ctx: &Ctx, _s: &State, e: Event) -> Effect<State, Event> {
+        match e {
+            Event::Expire => Effect::Stay(State::Expired, RenderPolicy::EditOrReply),
+        }
+    }
+}
+
+type Routes = telegram_botkit::router::compose::Routes<
+    TestAppCtx,
+    SimpleSession,
+    InMemStorage<SimpleSession>,
+    MemoryStore,
+>;
+
+fn routes() -> Routes {
+    Builder::new()
+        .route(Builder::scene::<QuoteScene>().codec())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn scheduled_event_restores_state_and_updates() {
+    let (bot, shutdown) = MockApi::default().start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+    let routes = routes();
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+
+    let eff = Effect::StayWithEffect(
+        State::Quote(10),
+        RenderPolicy::EditOrReply,
+        vec![UiEffect::schedule::<QuoteScene>(
+            &Event::Expire,
+            Duration::from_secs(0),
+        )],
+    );
+    apply_effect(&routes, &QuoteScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    let due = vp.take_due_events(10).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].scene_id, QuoteScene::ID);
+    assert_eq!(due[0].message_id, 100);
+    assert_eq!(due[0].user_id, Some(1));
+
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+    assert!(router.deliver(&ctx, &vp, &d, &due[0]).await.unwrap());
+
+    let mid = d
+        .get_or_default()
+        .await
+        .unwrap()
+        .ui_get_last_action_message_id()
+        .unwrap();
+    let (st, _) = restore_state(
+        &QuoteScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), MessageId(mid))),
+    )
+    .await;
+    assert_eq!(st, State::Expired);
+
+    // Claimed events are not handed out twice
+    assert!(vp.take_due_events(10).await.unwrap().is_empty());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn scheduled_event_leaves_the_session_where_the_user_moved_on() {
+    let api = MockApi::default();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+    let routes = routes();
+    let sctx = Ctx {
+        user_id: ctx.user_id(),
+        ..Default::default()
+    };
+
+    let eff = Effect::StayWithEffect(
+        State::Quote(10),
+        RenderPolicy::EditOrReply,
+        vec![UiEffect::schedule::<QuoteScene>(
+            &Event::Expire,
+            Duration::from_secs(0),
+        )],
+    );
+    apply_effect(&routes, &QuoteScene, &ctx, &vp, &d, &sctx, eff)
+        .await
+        .unwrap();
+
+    // The user opens another menu before the timer fires
+    let mut s = d.get_or_default().await.unwrap();
+    s.ui_set_last_action_message_id(Some(200));
+    s.ui_set_active_scene_id(Some("other".into()));
+    d.update(s).await.unwrap();
+    common::take(&api.bodies);
+
+    let due = vp.take_due_events(10).await.unwrap();
+    let router = RouterBuilder::new().with_routes(routes).build().unwrap();
+    assert!(router.deliver(&ctx, &vp, &d, &due[0]).await.unwrap());
+
+    let s = d.get_or_default().await.unwrap();
+    assert_eq!(s.ui_get_last_action_message_id(), Some(200));
+    assert_eq!(s.ui_get_active_scene_id().as_deref(), Some("other"));
+
+    let bodies = common::take(&api.bodies);
+    assert!(
+        bodies
+            .iter()
+            .any(|b| b.starts_with("EditMessageText") && b.contains("\"message_id\":100"))
+    );
+    assert!(!bodies.iter().any(|b| b.starts_with("SendMessage")));
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn scheduled_event_waits_for_delay() {
+    let vp = Viewport::new(MemoryStore::default());
+
+    vp.schedule_event(ChatId(1), 1, 5, QuoteScene::ID, "q:expire".into(), 60)
+        .await
+        .unwrap();
+
+    assert!(vp.take_due_events(10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn scheduled_event_for_expired_message_is_dropped() {
+    let (bot, shutdown) = MockApi::default().start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    vp.schedule_event(ChatId(1), 1, 55, QuoteScene::ID, "q:expire".into(), 0)
+        .await
+        .unwrap();
+    let due = vp.take_due_events(10).await.unwrap();
+
+    let router = RouterBuilder::new().with_routes(routes()).build().unwrap();
+    assert!(!router.deliver(&ctx, &vp, &d, &due[0]).await.unwrap());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn codec_route_decodes_event_buttons() {
+    let (bot, shutdown) = MockApi::default().start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let ctx = TestAppCtx::new(bot, ChatId(1));
+
+    let button =
+        telegram_botkit::ui::keyboard::event_button::<QuoteScene>("Expire", &Event::Expire);
+    let InlineKeyboardButtonKind::CallbackData(data) = button.kind else {
+        panic!("callback button expected");
+    };
+    assert_eq!(data, "q:expire");
+
+    let q = callback(&data);
+
+    assert!(routes().handle_cb(&ctx, &vp, &d, &q).await.unwrap());
+
+    let sctx = Ctx {
+        user_id: 1,
+        ..Default::default()
+    };
+    let (st, _) = restore_state(
+        &QuoteScene,
+        &vp,
+        &d,
+        &sctx,
+        Some((ChatId(1), MessageId(100))),
+    )
+    .await;
+    assert_eq!(st, State::Expired);
+
+    let _ = shutdown.send(());
+}
@@ -0,0 +1,51 @@
+use telegram_botkit::session::{NavEntry, SimpleSession, UiStore};
+
+fn entry(i: u16) -> NavEntry {
+    NavEntry {
+        scene_id: format!("s{i}"),
+        scene_version: 1,
+        state_json: None,
+    }
+}
+
+#[test]
+fn nav_stack_is_lifo() {
+    let mut s = SimpleSession::default();
+    s.ui_push_nav_entry(entry(1));
+    s.ui_push_nav_entry(entry(2));
+
+    assert_eq!(s.ui_get_nav_depth(), 2);
+    assert_eq!(s.ui_pop_nav_entry(), Some(entry(2)));
+    assert_eq!(s.ui_pop_nav_entry(), Some(entry(1)));
+    assert_eq!(s.ui_pop_nav_entry(), None);
+}
+
+#[test]
+fn nav_stack_drops_oldest_when_full() {
+    let mut s = SimpleSession::default();
+    for i in 0..20 {
+        s.ui_push_nav_entry(entry(i));
+    }
+
+    assert_eq!(s.ui_get_nav_depth(), 16);
+
+    let mut last = None;
+    while let Some(e) = s.ui_pop_nav_entry() {
+        last = Some(e);
+    }
+    assert_eq!(last, Some(entry(4)));
+}
+
+#[test]
+fn nav_stack_missing_in_old_sessions() {
+    let json = r#"{
+        "active_scene_id": "home",
+        "last_message_id": 1,
+        "input_prompt_message_id": null,
+        "reply_to_last_once": false,
+        "message_scenes": {}
+    }"#;
+    let s: SimpleSession = serde_json::from_str(json).unwrap();
+
+    assert_eq!(s.ui_get_nav_depth(), 0);
+}
@@ -30,6 +30,8 @@ async fn mapstore_roundtrip() {
         state_checksum: Some("deadbeef".into()),
         created_at: 0,
         ttl_secs: 60,
+        owner_id: None,
+        media: None,
     };
 
     // Save & Load through store directly
@@ -45,6 +47,7 @@ async fn mapstore_roundtrip() {
             state_json: Some("{\"state\":\"Root\"}".to_string()),
             state_ref: None,
             ttl_secs: 60,
+            owner_id: None,
         },
     )
     .await
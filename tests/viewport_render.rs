@@ -0,0 +1,168 @@
+mod common;
+
+use telegram_botkit::scene::{Media, MediaSource, RenderPolicy, View};
+use telegram_botkit::session::{SimpleSession, UiStore};
+use telegram_botkit::ui::keyboard::reply_rows;
+use telegram_botkit::viewport::{MetaSpec, Viewport, store::MemoryStore};
+
+use teloxide::dispatching::dialogue::{Dialogue, InMemStorage};
+use teloxide::types::{ChatId, ForceReply};
+
+use common::{MockApi, ok, sent_message, take};
+use std::sync::Arc;
+
+// Fresh message ids, so replaced menus are told apart
+fn numbered_api() -> MockApi {
+    MockApi::replying(|method, n| match method {
+        "DeleteMessage" => ok(serde_json::json!(true)),
+        _ => ok(sent_message(100 + n)),
+    })
+}
+
+fn photo_view(caption: &str, file_id: &str) -> View {
+    View::text(caption).with_media(Media::photo(MediaSource::FileId(file_id.into())))
+}
+
+fn spec() -> Option<MetaSpec> {
+    Some(MetaSpec::new("media", 1))
+}
+
+#[tokio::test]
+async fn media_views_pick_matching_requests() {
+    let api = numbered_api();
+    let calls = api.calls.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let chat = ChatId(1);
+    let pol = RenderPolicy::EditOrReply;
+
+    vp.apply_view(&bot, chat, &d, &photo_view("a", "p1"), pol, spec())
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["SendPhoto"]);
+
+    // Same photo: only the caption changes
+    vp.apply_view(&bot, chat, &d, &photo_view("b", "p1"), pol, spec())
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["EditMessageCaption"]);
+
+    vp.apply_view(&bot, chat, &d, &photo_view("b", "p2"), pol, spec())
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["EditMessageMedia"]);
+
+    // Media -> text can't be edited in place
+    vp.apply_view(&bot, chat, &d, &View::text("c"), pol, spec())
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["DeleteMessage", "SendMessage"]);
+
+    vp.apply_view(
+        &bot,
+        chat,
+        &d,
+        &View::text("d"),
+        RenderPolicy::EditOnly,
+        spec(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(take(&calls), ["EditMessageText"]);
+
+    let res = vp
+        .apply_view(
+            &bot,
+            chat,
+            &d,
+            &photo_view("e", "p1"),
+            RenderPolicy::EditOnly,
+            spec(),
+        )
+        .await;
+    // EditOnly never sends, even when the kind changes
+    assert!(res.is_err());
+    assert!(take(&calls).is_empty());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn media_prompt_is_sent_as_new_message() {
+    let api = numbered_api();
+    let calls = api.calls.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+
+    let view = View::text("Report").with_media(Media::document(MediaSource::Bytes {
+        file_name: "report.csv".into(),
+        data: b"a,b\n1,2\n".to_vec(),
+    }));
+    vp.apply_view(&bot, ChatId(1), &d, &view, RenderPolicy::SendNew, None)
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["SendDocument"]);
+
+    let s = d.get_or_default().await.unwrap();
+    assert!(s.ui_get_input_prompt_message_id().is_some());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn force_reply_prompt_skips_close_row() {
+    let api = numbered_api();
+    let calls = api.calls.clone();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+
+    let view = View::text("Enter amount").with_markup(ForceReply::new());
+    vp.apply_view(&bot, ChatId(1), &d, &view, RenderPolicy::SendNew, None)
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["SendMessage"]);
+
+    let body = bodies.lock().unwrap().pop().unwrap();
+    assert!(body.contains("force_reply"));
+    assert!(!body.contains(telegram_botkit::ui::callback::CANCEL));
+
+    let s = d.get_or_default().await.unwrap();
+    assert!(s.ui_get_input_prompt_message_id().is_some());
+
+    let _ = shutdown.send(());
+}
+
+#[tokio::test]
+async fn reply_keyboard_is_sent_not_edited() {
+    let api = numbered_api();
+    let calls = api.calls.clone();
+    let bodies = api.bodies.clone();
+    let (bot, shutdown) = api.start().await;
+    let vp = Viewport::new(MemoryStore::default());
+    let storage: Arc<InMemStorage<SimpleSession>> = InMemStorage::new();
+    let d = Dialogue::new(storage, ChatId(1));
+    let pol = RenderPolicy::EditOrReply;
+
+    vp.apply_view(&bot, ChatId(1), &d, &View::text("menu"), pol, None)
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["SendMessage"]);
+
+    let view = View::text("menu").with_markup(reply_rows(vec![vec!["📊 Stats", "⚙️ Settings"]]));
+    vp.apply_view(&bot, ChatId(1), &d, &view, pol, None)
+        .await
+        .unwrap();
+    assert_eq!(take(&calls), ["SendMessage"]);
+
+    let body = bodies.lock().unwrap().pop().unwrap();
+    assert!(body.contains("keyboard"));
+
+    let _ = shutdown.send(());
+}